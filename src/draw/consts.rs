@@ -5,6 +5,10 @@ pub const PPCM: f64 = 35.43307086614173 * 0.577350269258;
 pub const C: f64 = 0.551915024494;
 pub const PATH_WIDTH: f64 = 0.1;
 pub const LINE_WIDTH: f64 = 0.02;
+/// Length of the dashes and gaps of narrow gauge track
+pub const NARROW_DASH: f64 = 0.12;
+/// Width of the white line that splits dual gauge track in two
+pub const DUAL_GAP: f64 = PATH_WIDTH / 3.0;
 pub const TOKEN_SIZE: f64 = 0.269;
 pub const STOP_SIZE:  f64 = 0.11;
 pub const STOP_TEXT_DIST: f64 = 0.3;
@@ -16,3 +20,13 @@ pub const REVENUE_WIDTH: f64 = 0.35;
 pub const REVENUE_HEIGHT: f64 = 0.2;
 pub const BORDER: f64 = 0.5;
 pub const TERRAIN_SIZE: f64 = 0.2;
+pub const COAST_WIDTH: f64 = 0.15;
+pub const BORDER_WIDTH: f64 = 0.05;
+pub const EDGE_WAVE: f64 = 0.06;
+pub const EDGE_ICON_DIST: f64 = 0.25;
+pub const EDGE_TEXT_DIST: f64 = 0.5;
+pub const ICON_SIZE: f64 = 0.4;
+pub const AREA_TEXT_DIST: f64 = 0.3;
+/// Distance from the center of a tile of the labels of the preview axes
+pub const AXIS_LABEL_DIST: f64 = 1.1;
+pub const TEXT_MARKER_SIZE: f64 = 0.03;
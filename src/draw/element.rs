@@ -2,7 +2,6 @@ extern crate nalgebra as na;
 extern crate svg;
 
 use std::f64::consts::PI;
-use std::process;
 use ::svg::node;
 use ::svg::node::element;
 use ::svg::node::element::path::Data;
@@ -60,26 +59,7 @@ pub fn draw_barrier(barrier: &game::Barrier,
                     pos: &Vector2<f64>,
                     map: &game::Map) -> element::Line {
     let basis = get_basis(&map.orientation);
-    let points = [
-        Vector3::new( 0.0,  0.0,  1.0),
-        Vector3::new( 0.0,  1.0,  0.0),
-        Vector3::new( 1.0,  0.0,  0.0),
-        Vector3::new( 0.0,  0.0, -1.0),
-        Vector3::new( 0.0, -1.0,  0.0),
-        Vector3::new(-1.0,  0.0,  0.0),
-    ];
-    let coords = match barrier.side.as_str() {
-        "N"  => (points[0], points[1]),
-        "NE" => (points[1], points[2]),
-        "SE" => (points[2], points[3]),
-        "S"  => (points[3], points[4]),
-        "SW" => (points[4], points[5]),
-        "NW" => (points[5], points[0]),
-        s => {
-            eprintln!("Unknown tile side {}", s);
-            process::exit(1);
-        }
-    };
+    let coords = hex_side_corners(&barrier.side);
     let start = (pos + basis * coords.0) * scale(&map);
     let end = (pos + basis * coords.1) * scale(&map);
 
@@ -93,6 +73,230 @@ pub fn draw_barrier(barrier: &game::Barrier,
         .set("stroke-linecap", "round")
 }
 
+/// Draw the outline of an area that spans multiple hexes
+///
+/// `sides` are the sides of the hexes of the area that are not shared with
+/// another hex of the area, together with the center of their hex. They are
+/// joined into closed shapes.
+pub fn draw_area(area: &game::Area,
+                 sides: &[(Vector2<f64>, &str)],
+                 map: &game::Map) -> element::Path {
+    let basis = get_basis(&map.orientation);
+    let mut segments: Vec<(Vector2<f64>, Vector2<f64>)> = sides.iter()
+        .map(|&(center, side)| {
+            let (start, end) = hex_side_corners(side);
+            (basis * start + center, basis * end + center)
+        })
+        .collect();
+    let mut data = Data::new();
+    while !segments.is_empty() {
+        let (start, mut end) = segments.remove(0);
+        data = data.move_to(point_to_tuple(scale(map) * start))
+            .line_to(point_to_tuple(scale(map) * end));
+        // The sides of all hexes go round in the same direction, so each
+        // side of the outline starts where the previous one ends
+        while let Some(i) = segments.iter()
+                .position(|&(next, _)| (next - end).norm() < 1e-6) {
+            end = segments.remove(i).1;
+            if (end - start).norm() < 1e-6 {
+                break;
+            }
+            data = data.line_to(point_to_tuple(scale(map) * end));
+        }
+        data = data.close();
+    }
+    element::Path::new()
+        .set("d", data)
+        .set("data-area", escape(&area.name))
+        .set("fill", "none")
+        .set("stroke", "black")
+        .set("stroke-width", LINE_WIDTH * scale(map))
+}
+
+/// Draw the name and revenue of an area that spans multiple hexes
+pub fn draw_area_label(area: &game::Area,
+                       centers: &[Vector2<f64>],
+                       map: &game::Map) -> element::Group {
+    let basis = get_basis(&map.orientation);
+    let mut g = element::Group::new();
+    let center = centers.iter().fold(Vector2::new(0.0, 0.0), |acc, c| acc + c)
+        / centers.len() as f64;
+    let text_pos = scale(map) * (center + basis
+                                 * Vector3::new(0.0, AREA_TEXT_DIST,
+                                                AREA_TEXT_DIST));
+    let mut text = draw_text(&area.name, &text_pos, &TextAnchor::Middle,
+                             None, Some(600));
+    if let Orientation::Vertical = map.orientation {
+        text = text.set("transform", format!("rotate(-30 {} {})",
+                                             text_pos.x, text_pos.y));
+    }
+    g = g.add(text);
+    if let Some(ref track) = area.revenue {
+        g = g.add(draw_revenue_track(track, &center, map));
+    }
+    g
+}
+
+/// Draw decorations on the side of a hex
+pub fn draw_edge_decoration(decoration: &game::EdgeDecoration,
+                            pos: &Vector2<f64>,
+                            map: &game::Map) -> element::Group {
+    let basis = get_basis(&map.orientation);
+    let (corner1, corner2) = hex_side_corners(&decoration.side);
+    let start = pos + basis * corner1;
+    let end = pos + basis * corner2;
+    let middle = (start + end) / 2.0;
+    // Unit vector pointing from the side to the center of the hex
+    let inward = (pos - middle).normalize();
+    let along = (end - start).normalize();
+    let mut g = element::Group::new();
+
+    match decoration.decoration_type {
+        game::EdgeDecorationType::Water => {
+            let step = (end - start) / 4.0;
+            let control = start + 0.5 * step + EDGE_WAVE * inward;
+            let pos1 = scale(map) * (start + step);
+            let pos2 = scale(map) * (start + 2.0 * step);
+            let pos3 = scale(map) * (start + 3.0 * step);
+            let pos4 = scale(map) * end;
+            let data = Data::new()
+                .move_to(point_to_tuple(scale(map) * start))
+                .quadratic_curve_to((scale(map) * control.x,
+                                     scale(map) * control.y,
+                                     pos1.x, pos1.y))
+                .smooth_quadratic_curve_to((pos2.x, pos2.y))
+                .smooth_quadratic_curve_to((pos3.x, pos3.y))
+                .smooth_quadratic_curve_to((pos4.x, pos4.y));
+            g = g.add(element::Path::new()
+                      .set("d", data)
+                      .set("fill", "none")
+                      .set("stroke", tile::colors::BLUE.value())
+                      .set("stroke-width", 2.0 * LINE_WIDTH * scale(map)));
+        }
+        game::EdgeDecorationType::Coast => {
+            let data = Data::new()
+                .move_to(point_to_tuple(scale(map) * start))
+                .line_to(point_to_tuple(scale(map) * end))
+                .line_to(point_to_tuple(
+                        scale(map) * (end + COAST_WIDTH * (pos - end))))
+                .line_to(point_to_tuple(
+                        scale(map) * (start + COAST_WIDTH * (pos - start))))
+                .close();
+            g = g.add(element::Path::new()
+                      .set("d", data)
+                      .set("fill", tile::colors::BLUE.value())
+                      .set("fill-opacity", 0.5)
+                      .set("stroke", "none"));
+        }
+        game::EdgeDecorationType::Port => {
+            // Anchor: ring at the top, shank and a curved fluke at the bottom
+            let top = middle + EDGE_ICON_DIST * inward
+                + 0.5 * TERRAIN_SIZE * Vector2::new(0.0, -1.0);
+            let bottom = middle + EDGE_ICON_DIST * inward
+                + 0.5 * TERRAIN_SIZE * Vector2::new(0.0, 1.0);
+            let left = bottom + TERRAIN_SIZE * Vector2::new(-0.5, -0.3);
+            let right = bottom + TERRAIN_SIZE * Vector2::new(0.5, -0.3);
+            let bar = top + 0.3 * TERRAIN_SIZE * Vector2::new(0.0, 1.0);
+            let shank = Data::new()
+                .move_to(point_to_tuple(scale(map) * top))
+                .line_to(point_to_tuple(scale(map) * bottom))
+                .move_to(point_to_tuple(scale(map)
+                    * (bar + 0.3 * TERRAIN_SIZE * Vector2::new(-1.0, 0.0))))
+                .line_to(point_to_tuple(scale(map)
+                    * (bar + 0.3 * TERRAIN_SIZE * Vector2::new(1.0, 0.0))))
+                .move_to(point_to_tuple(scale(map) * left))
+                .quadratic_curve_to((scale(map) * bottom.x,
+                                     scale(map) * (bottom.y + 0.3
+                                                   * TERRAIN_SIZE),
+                                     scale(map) * right.x,
+                                     scale(map) * right.y));
+            g = g.add(element::Path::new()
+                      .set("d", shank)
+                      .set("fill", "none")
+                      .set("stroke", "black")
+                      .set("stroke-width", 1.5 * LINE_WIDTH * scale(map)))
+                .add(draw_circle(&(scale(map) * top),
+                                 0.15 * TERRAIN_SIZE * scale(map),
+                                 "white", "black",
+                                 1.5 * LINE_WIDTH * scale(map)));
+        }
+        game::EdgeDecorationType::Ferry => {
+            // Hull of a boat with a funnel on top
+            let center = middle + EDGE_ICON_DIST * inward;
+            let hull = Data::new()
+                .move_to(point_to_tuple(scale(map)
+                    * (center + TERRAIN_SIZE * Vector2::new(-0.8, 0.0))))
+                .line_to(point_to_tuple(scale(map)
+                    * (center + TERRAIN_SIZE * Vector2::new(0.8, 0.0))))
+                .line_to(point_to_tuple(scale(map)
+                    * (center + TERRAIN_SIZE * Vector2::new(0.5, 0.4))))
+                .line_to(point_to_tuple(scale(map)
+                    * (center + TERRAIN_SIZE * Vector2::new(-0.5, 0.4))))
+                .close();
+            let funnel = scale(map)
+                * (center + TERRAIN_SIZE * Vector2::new(-0.15, -0.5));
+            g = g.add(element::Rectangle::new()
+                      .set("x", funnel.x)
+                      .set("y", funnel.y)
+                      .set("width", 0.3 * TERRAIN_SIZE * scale(map))
+                      .set("height", 0.5 * TERRAIN_SIZE * scale(map))
+                      .set("fill", "black"))
+                .add(element::Path::new()
+                     .set("d", hull)
+                     .set("fill", tile::colors::BLUE.value())
+                     .set("stroke", "black")
+                     .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
+        game::EdgeDecorationType::Tunnel => {
+            // Half circle that opens up towards the side
+            let pos1 = middle - TERRAIN_SIZE * along;
+            let pos2 = middle + TERRAIN_SIZE * along;
+            let control1 = pos1 + 1.3 * TERRAIN_SIZE * inward;
+            let control2 = pos2 + 1.3 * TERRAIN_SIZE * inward;
+            let (x1, y1) = point_to_tuple(scale(map) * control1);
+            let (x2, y2) = point_to_tuple(scale(map) * control2);
+            let (x3, y3) = point_to_tuple(scale(map) * pos2);
+            let data = Data::new()
+                .move_to(point_to_tuple(scale(map) * pos1))
+                .cubic_curve_to((x1, y1, x2, y2, x3, y3))
+                .close();
+            g = g.add(element::Path::new()
+                      .set("d", data)
+                      .set("fill", "dimgrey")
+                      .set("stroke", "black")
+                      .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
+        game::EdgeDecorationType::Border => {
+            let start = scale(map) * start;
+            let end = scale(map) * end;
+            g = g.add(element::Line::new()
+                      .set("x1", start.x)
+                      .set("y1", start.y)
+                      .set("x2", end.x)
+                      .set("y2", end.y)
+                      .set("stroke", "black")
+                      .set("stroke-width", BORDER_WIDTH * scale(map))
+                      .set("stroke-dasharray",
+                           format!("{} {}", 3.0 * BORDER_WIDTH * scale(map),
+                                   2.0 * BORDER_WIDTH * scale(map))));
+        }
+    }
+
+    // Draw the cost of crossing the side
+    if let Some(ref cost) = decoration.cost {
+        let text_pos = scale(map) * (middle + EDGE_TEXT_DIST * inward);
+        let mut text = draw_text(cost, &text_pos, &TextAnchor::Middle,
+                                 None, None);
+        if let Orientation::Vertical = map.orientation {
+            text = text.set("transform", format!("rotate(-30 {} {})",
+                                                 text_pos.x, text_pos.y));
+        }
+        g = g.add(text);
+    }
+
+    g
+}
+
 /// Draw a city
 pub fn draw_city(city: tile::City,
                     center: Vector2<f64>,
@@ -264,12 +468,18 @@ pub fn draw_city_contrast(city: tile::City,
 }
 
 /// Draw the coordinate system around a map
+///
+/// Coordinates are only drawn for the rows and columns that contain at least
+/// one of the `locations`, `offset` is the position of the center of the hex
+/// at location (0, 0).
 pub fn draw_coordinate_system(game: &game::Game,
                               options: &::Options,
+                              locations: &[(u32, u32)],
+                              offset: &Vector2<f64>,
                               width: f64,
                               height: f64) -> element::Group {
-    let hoffset: f64;
-    let voffset: f64 = BORDER+ 1.0;
+    let hoffset = offset.x;
+    let voffset = offset.y;
     let hstride: f64;
     let vstride: f64;
     let mut hnums: u32 = 1;
@@ -279,7 +489,6 @@ pub fn draw_coordinate_system(game: &game::Game,
             if !options.debug_coordinates {
                 vnums = 2;
             }
-            hoffset = BORDER + 1.0;
             hstride = 1.5;
             vstride = 3.0_f64.sqrt() / vnums as f64;
         }
@@ -287,11 +496,23 @@ pub fn draw_coordinate_system(game: &game::Game,
             if !options.debug_coordinates {
                 hnums = 2;
             }
-            hoffset = BORDER + 0.5 * 3.0_f64.sqrt();
             hstride = 3.0_f64.sqrt() / hnums as f64;
             vstride = 1.5;
         }
     }
+    // Determine which columns and rows have hexes in them, every other
+    // column or row is offset by half a hex
+    let shift = game.map.coordinates.parity.shift();
+    let columns: Vec<u32> = locations.iter()
+        .map(|&(x, y)| if hnums == 2 { 2 * x + (y + shift) % 2 } else { x })
+        .collect();
+    let rows: Vec<u32> = locations.iter()
+        .map(|&(x, y)| if vnums == 2 { 2 * y + (x + shift) % 2 } else { y })
+        .collect();
+    // Counting starts half a hex before the first hex when the first column
+    // or row is shifted
+    let hoffset = hoffset - (hnums - 1) as f64 * shift as f64 * hstride;
+    let voffset = voffset - (vnums - 1) as f64 * shift as f64 * vstride;
     let mut border = element::Group::new()
         .add(element::Rectangle::new()
             .set("x", BORDER * scale(&game.map))
@@ -301,13 +522,15 @@ pub fn draw_coordinate_system(game: &game::Game,
             .set("fill", "none")
             .set("stroke", "black")
             .set("stroke-width", LINE_WIDTH * scale(&game.map)));
-    for x in 0..(game.map.width * hnums) {
+    for x in 0..(columns.iter().max().unwrap_or(&0) + 1) {
+        if !columns.contains(&x) {
+            continue;
+        }
         let text = if options.debug_coordinates {
             x.to_string()
-        } else if let Orientation::Horizontal = game.map.orientation {
-            number_to_letter(x)
         } else {
-            (x + 1).to_string()
+            game.map.coordinates.label(x, game::Axis::Columns,
+                                       &game.map.orientation)
         };
         let x1 = Vector2::new(x as f64 * hstride + hoffset,
                               0.5 * BORDER) * scale(&game.map);
@@ -319,13 +542,15 @@ pub fn draw_coordinate_system(game: &game::Game,
             .add(draw_text(&text, &x2, &TextAnchor::Middle,
                                     Some("16pt"), Some(600)));
     }
-    for y in 0..(game.map.height * vnums) {
+    for y in 0..(rows.iter().max().unwrap_or(&0) + 1) {
+        if !rows.contains(&y) {
+            continue;
+        }
         let text = if options.debug_coordinates {
             y.to_string()
-        } else if let Orientation::Vertical = game.map.orientation {
-            number_to_letter(y)
         } else {
-            (y + 1).to_string()
+            game.map.coordinates.label(y, game::Axis::Rows,
+                                       &game.map.orientation)
         };
         let y1 = Vector2::new(0.5 * BORDER,
                               y as f64 * vstride + voffset) * scale(&game.map);
@@ -340,6 +565,45 @@ pub fn draw_coordinate_system(game: &game::Game,
     border
 }
 
+/// Draw the axes of hexagon-space on a tile
+pub fn draw_axes(center: &Vector2<f64>,
+                 info: &game::Map,
+                 rotation: &f64) -> element::Group {
+    let basis = get_basis(&info.orientation);
+    let rot = rotate(rotation);
+    let mut g = element::Group::new();
+    let axes = [("x", Vector3::new(1.0, 0.0, 0.0), "red"),
+                ("y", Vector3::new(0.0, 1.0, 0.0), "green"),
+                ("z", Vector3::new(0.0, 0.0, 1.0), "blue")];
+    for &(name, ref axis, color) in axes.iter() {
+        let start = scale(info) * center;
+        let end = scale(info) * (rot * basis * axis + center);
+        let label = scale(info) * (rot * basis * axis * AXIS_LABEL_DIST
+                                   + center);
+        let data = Data::new()
+            .move_to(point_to_tuple(start))
+            .line_to(point_to_tuple(end));
+        g = g.add(element::Path::new()
+                  .set("d", data)
+                  .set("stroke", color)
+                  .set("stroke-width", LINE_WIDTH * scale(info)))
+            .add(draw_text(name, &label, &TextAnchor::Middle, None, None)
+                 .set("fill", color));
+    }
+    g
+}
+
+/// Draw a dot where text is placed
+pub fn draw_text_marker(pos: &Vector3<f64>,
+                        center: &Vector2<f64>,
+                        info: &game::Map,
+                        rotation: &f64) -> element::Circle {
+    let basis = get_basis(&info.orientation);
+    let rot = rotate(rotation);
+    draw_circle(&(scale(info) * (rot * basis * pos + center)),
+                TEXT_MARKER_SIZE * scale(info), "red", "none", 0.0)
+}
+
 /// Draw a small black circle in the middle of a tile to connect paths nicely
 pub fn draw_lawson(center: Vector2<f64>,
                    info: &game::Map) -> element::Circle {
@@ -359,9 +623,24 @@ pub fn draw_path(path: &tile::Path,
     if path.is_bridge() {
         g = g.add(draw_path_contrast(path, center, info, rotation));
     }
-    g.add(draw_path_helper(path, center, info, rotation)
-          .set("stroke", "black")
-          .set("stroke-width", PATH_WIDTH * scale(&info)))
+    let line = draw_path_helper(path, center, info, rotation)
+        .set("stroke-width", PATH_WIDTH * scale(info));
+    match path.track() {
+        tile::TrackType::Standard => g.add(line.set("stroke", "black")),
+        tile::TrackType::Narrow => {
+            let dash = NARROW_DASH * scale(info);
+            g.add(line.set("stroke", "black")
+                  .set("stroke-dasharray", format!("{} {}", dash, dash)))
+        }
+        // A white line down the middle leaves two thin black lines
+        tile::TrackType::Dual => g
+            .add(line.set("stroke", "black"))
+            .add(draw_path_helper(path, center, info, rotation)
+                 .set("stroke", "white")
+                 .set("stroke-width", DUAL_GAP * scale(info))),
+        tile::TrackType::Ferry =>
+            g.add(line.set("stroke", tile::colors::BLUE.value())),
+    }
 }
 
 /// Draws the white contrast lines around a path
@@ -380,53 +659,37 @@ pub fn draw_revenue_track(track: &tile::RevenueTrack,
                           map: &game::Map) -> element::Group {
     let basis = get_basis(&map.orientation);
     // Determine position
-    let mut blocks = 1.0;
-    if let Some(_) = track.green {
-        blocks += 1.0;
-    }
-    if let Some(_) = track.russet {
-        blocks += 1.0;
-    }
-    if let Some(_) = track.grey {
-        blocks += 1.0;
-    }
+    let blocks = track.phases.len() as f64;
     let topleft = scale(&map) * ((basis * track.position() + center) -
          Vector2::new(blocks / 2.0 * REVENUE_WIDTH, REVENUE_HEIGHT / 2.0));
 
     // Draw the track
     let textpos = topleft + scale(&map) *
         Vector2::new(REVENUE_WIDTH / 2.0, REVENUE_HEIGHT * 0.5);
-    let mut g = element::Group::new()
-        .add(element::Rectangle::new()
-             .set("x", topleft.x)
-             .set("y", topleft.y)
-             .set("width", REVENUE_WIDTH * scale(&map))
-             .set("height", REVENUE_HEIGHT * scale(&map))
-             .set("fill", tile::colors::YELLOW.value()))
-        .add(draw_text(&track.yellow.to_string(), &textpos,
-                       &TextAnchor::Middle, None, None));
+    let labelpos = textpos - scale(map) * Vector2::new(0.0, REVENUE_HEIGHT);
+    let mut g = element::Group::new();
     if let Orientation::Vertical = map.orientation {
         let center = scale(&map) * center;
         g = g.set("transform",
                   format!("rotate(-30 {} {})", center.x, center.y));
     }
 
-    let blocks = [(&track.green,  tile::colors::GREEN),
-                  (&track.russet, tile::colors::RUSSET),
-                  (&track.grey,   tile::colors::GREY)];
     let offset = scale(&map) * Vector2::new(REVENUE_WIDTH, 0.0);
-    let mut i = 1.0;
-    for block in blocks.iter() {
-        if let &(&Some(ref text), ref color) = block {
-            g = g.add(element::Rectangle::new()
-                  .set("x", topleft.x + i * offset.x)
-                  .set("y", topleft.y)
-                  .set("width", REVENUE_WIDTH * scale(&map))
-                  .set("height", REVENUE_HEIGHT * scale(&map))
-                  .set("fill", color.value()))
-                .add(draw_text(&text.to_string(), &(textpos + i * offset),
-                               &TextAnchor::Middle, None, None));
-            i += 1.0;
+    for (i, phase) in track.phases.iter().enumerate() {
+        let i = i as f64;
+        g = g.add(element::Rectangle::new()
+              .set("x", topleft.x + i * offset.x)
+              .set("y", topleft.y)
+              .set("width", REVENUE_WIDTH * scale(map))
+              .set("height", REVENUE_HEIGHT * scale(map))
+              .set("fill", tile::colors::name_to_color(&phase.color).value())
+              .set("stroke", "black")
+              .set("stroke-width", LINE_WIDTH * scale(map)))
+            .add(draw_text(&phase.value, &(textpos + i * offset),
+                           &TextAnchor::Middle, None, None));
+        if let Some(ref label) = phase.label {
+            g = g.add(draw_text(label, &(labelpos + i * offset),
+                                &TextAnchor::Middle, Some("60%"), None));
         }
     }
 
@@ -596,11 +859,136 @@ pub fn draw_terrain(terrain: &tile::Terrain,
                      .set("stroke", "blue")
                      .set("stroke-width", LINE_WIDTH * scale(&map)));
         }
+        &tile::TerrainType::Forest => {
+            let pos1 = Vector3::new(-0.5 * TERRAIN_SIZE, 0.0, 0.0);
+            let pos2 = Vector3::new( 0.5 * TERRAIN_SIZE, 0.0, 0.0);
+            let pos3 = Vector3::new(0.0, TERRAIN_SIZE, TERRAIN_SIZE);
+            let trunk1 = scale(map) * center;
+            let trunk2 = scale(map) * (center + basis
+                * Vector3::new(0.0, -0.3 * TERRAIN_SIZE, -0.3 * TERRAIN_SIZE));
+            let path = Data::new()
+                .move_to(point_to_tuple(scale(map) * (basis * pos1 + center)))
+                .line_to(point_to_tuple(scale(map) * (basis * pos2 + center)))
+                .line_to(point_to_tuple(scale(map) * (basis * pos3 + center)))
+                .close();
+            g = g.add(element::Line::new()
+                      .set("x1", trunk1.x).set("y1", trunk1.y)
+                      .set("x2", trunk2.x).set("y2", trunk2.y)
+                      .set("stroke", tile::colors::BROWN.value())
+                      .set("stroke-width", 2.0 * LINE_WIDTH * scale(map)))
+                .add(element::Path::new()
+                     .set("d", path)
+                     .set("fill", "darkgreen")
+                     .set("stroke", "black")
+                     .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
+        &tile::TerrainType::Lake => {
+            let pos = scale(map) * (center + basis
+                * Vector3::new(0.0, 0.4 * TERRAIN_SIZE, 0.4 * TERRAIN_SIZE));
+            g = g.add(element::Ellipse::new()
+                      .set("cx", pos.x)
+                      .set("cy", pos.y)
+                      .set("rx", TERRAIN_SIZE * scale(map))
+                      .set("ry", 0.5 * TERRAIN_SIZE * scale(map))
+                      .set("fill", tile::colors::BLUE.value())
+                      .set("stroke", "black")
+                      .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
+        &tile::TerrainType::Desert => {
+            let pos1 = scale(map) * (center + basis
+                * Vector3::new(-TERRAIN_SIZE, 0.0, 0.0));
+            let pos2 = scale(map) * center;
+            let pos3 = scale(map) * (center + basis
+                * Vector3::new(TERRAIN_SIZE, 0.0, 0.0));
+            let control1 = scale(map) * (center + basis
+                * Vector3::new(-0.5 * TERRAIN_SIZE, 0.8 * TERRAIN_SIZE,
+                               0.8 * TERRAIN_SIZE));
+            let control2 = scale(map) * (center + basis
+                * Vector3::new(0.5 * TERRAIN_SIZE, 0.5 * TERRAIN_SIZE,
+                               0.5 * TERRAIN_SIZE));
+            let path = Data::new()
+                .move_to((pos1.x, pos1.y))
+                .quadratic_curve_to((control1.x, control1.y, pos2.x, pos2.y))
+                .quadratic_curve_to((control2.x, control2.y, pos3.x, pos3.y))
+                .close();
+            g = g.add(element::Path::new()
+                      .set("d", path)
+                      .set("fill", "khaki")
+                      .set("stroke", "goldenrod")
+                      .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
+        &tile::TerrainType::Swamp => {
+            let pos1 = scale(map) * (center + basis
+                * Vector3::new(-TERRAIN_SIZE, 0.0, 0.0));
+            let pos2 = scale(map) * (center + basis
+                * Vector3::new( TERRAIN_SIZE, 0.0, 0.0));
+            let reeds = [
+                Vector3::new(-0.4 * TERRAIN_SIZE, 0.6 * TERRAIN_SIZE,
+                             0.6 * TERRAIN_SIZE),
+                Vector3::new(0.0, 0.9 * TERRAIN_SIZE, 0.9 * TERRAIN_SIZE),
+                Vector3::new(0.4 * TERRAIN_SIZE, 0.6 * TERRAIN_SIZE,
+                             0.6 * TERRAIN_SIZE),
+            ];
+            g = g.add(element::Line::new()
+                      .set("x1", pos1.x).set("y1", pos1.y)
+                      .set("x2", pos2.x).set("y2", pos2.y)
+                      .set("stroke", "blue")
+                      .set("stroke-width", LINE_WIDTH * scale(map)));
+            let base = scale(map) * center;
+            for reed in reeds.iter() {
+                let top = scale(map) * (center + basis * reed);
+                g = g.add(element::Line::new()
+                          .set("x1", base.x).set("y1", base.y)
+                          .set("x2", top.x).set("y2", top.y)
+                          .set("stroke", "darkolivegreen")
+                          .set("stroke-width", LINE_WIDTH * scale(map)));
+            }
+        }
+        &tile::TerrainType::Tunnel => {
+            let pos1 = scale(map) * (center + basis
+                * Vector3::new(-0.6 * TERRAIN_SIZE, 0.0, 0.0));
+            let pos2 = scale(map) * (center + basis
+                * Vector3::new( 0.6 * TERRAIN_SIZE, 0.0, 0.0));
+            let control1 = scale(map) * (center + basis
+                * Vector3::new(-0.6 * TERRAIN_SIZE, 0.8 * TERRAIN_SIZE,
+                               0.8 * TERRAIN_SIZE));
+            let control2 = scale(map) * (center + basis
+                * Vector3::new(0.6 * TERRAIN_SIZE, 0.8 * TERRAIN_SIZE,
+                               0.8 * TERRAIN_SIZE));
+            let path = Data::new()
+                .move_to((pos1.x, pos1.y))
+                .cubic_curve_to((control1.x, control1.y,
+                                 control2.x, control2.y,
+                                 pos2.x, pos2.y))
+                .close();
+            g = g.add(element::Path::new()
+                      .set("d", path)
+                      .set("fill", "dimgrey")
+                      .set("stroke", "black")
+                      .set("stroke-width", LINE_WIDTH * scale(map)));
+        }
     }
 
     g
 }
 
+/// Draw an icon that was embedded in the document
+pub fn draw_icon(icon: &tile::Icon,
+                 center: &Vector2<f64>,
+                 map: &game::Map,
+                 rotation: &f64) -> element::Use {
+    let basis = get_basis(&map.orientation);
+    let rot = rotate(rotation);
+    let size = ICON_SIZE * icon.scale * scale(map);
+    let pos = scale(map) * (rot * basis * icon.position() + center);
+    element::Use::new()
+        .set("xlink:href", format!("#icon-{}", icon.name))
+        .set("x", pos.x - size / 2.0)
+        .set("y", pos.y - size / 2.0)
+        .set("width", size)
+        .set("height", size)
+}
+
 /// Draw text
 pub fn draw_text(text: &str,
                  pos: &Vector2<f64>,
@@ -621,7 +1009,7 @@ pub fn draw_text(text: &str,
         style.push_str(format!("font-weight:{};", weight).as_str());
     }
     element::Text::new()
-        .add(node::Text::new(text.replace("&", "&amp;")))
+        .add(node::Text::new(escape(text)))
         .set("x", pos.x)
         .set("y", pos.y)
         .set("style", style)
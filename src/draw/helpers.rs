@@ -1,6 +1,5 @@
 extern crate nalgebra as na;
 
-use std::char;
 use super::svg::node::element;
 use super::svg::node::element::path::Data;
 use draw::consts::*;
@@ -57,6 +56,31 @@ pub fn draw_hex_background(center: na::Vector2<f64>,
         .set("stroke", "none")
 }
 
+/// The two corners, in hexagon-space, that make up a side of a hex
+///
+/// # Panics
+///
+/// On invalid sides, the sides on a map are checked when it is read
+pub fn hex_side_corners(side: &str) -> (na::Vector3<f64>, na::Vector3<f64>) {
+    let points = [
+        na::Vector3::new( 0.0,  0.0,  1.0),
+        na::Vector3::new( 0.0,  1.0,  0.0),
+        na::Vector3::new( 1.0,  0.0,  0.0),
+        na::Vector3::new( 0.0,  0.0, -1.0),
+        na::Vector3::new( 0.0, -1.0,  0.0),
+        na::Vector3::new(-1.0,  0.0,  0.0),
+    ];
+    match side {
+        "N"  => (points[0], points[1]),
+        "NE" => (points[1], points[2]),
+        "SE" => (points[2], points[3]),
+        "S"  => (points[3], points[4]),
+        "SW" => (points[4], points[5]),
+        "NW" => (points[5], points[0]),
+        s => panic!("Unknown tile side {}", s),
+    }
+}
+
 /// Helper for drawing the paths, does the actual point calculation
 pub fn draw_path_helper(path: &tile::Path,
                         center: &na::Vector2<f64>,
@@ -167,11 +191,10 @@ pub fn point_to_tuple(p: na::Vector2<f64>) -> (f64, f64) {
     (p.x, p.y)
 }
 
-/// Converts a number to a coordinate letter 'A', 'B', .. 'AA', 'AB' etc.
-pub fn number_to_letter(n: u32) -> String {
-    match n {
-        n if n < 26 =>
-            char::from_digit(n + 10, 36).unwrap().to_string().to_uppercase(),
-        n => number_to_letter(n / 26 - 1) + &number_to_letter(n % 26),
-    }
+/// Escapes text so that it can be used as an attribute value or as text
+pub fn escape(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
 }
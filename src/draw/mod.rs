@@ -3,8 +3,8 @@ extern crate nalgebra as na;
 
 use std::collections::HashMap;
 use std::f64::consts::PI;
-use std::ops::Deref;
-use std::process;
+use std::str;
+use self::svg::node;
 use self::svg::node::element::Group;
 use tile;
 use tile::TileSpec;
@@ -13,10 +13,163 @@ use self::na::Vector2;
 use game::Orientation;
 
 mod helpers;
-mod consts;
+pub use self::helpers::escape;
+pub mod consts;
 mod element;
 
 const TILES_PER_ROW: f64 = 4.0;
+/// Namespace that is required for referring to icons
+pub const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// Embeds the icons of a game so that tiles can refer to them
+pub fn draw_icon_definitions(icons: &HashMap<String, game::IconSymbol>)
+        -> node::element::Definitions {
+    let mut defs = node::element::Definitions::new();
+    let mut names: Vec<_> = icons.keys().collect();
+    names.sort();
+    for name in names {
+        let icon = &icons[name];
+        defs = defs.add(node::element::Symbol::new()
+                        .set("id", format!("icon-{}", name))
+                        .set("viewBox", icon.view_box.as_str())
+                        .add(node::Text::new(icon.content.as_str())));
+    }
+    defs
+}
+
+/// Parts of a map that are drawn on top of each other, from the bottom up
+///
+/// Each layer is drawn for the whole map before the next one, so nothing on
+/// one hex can cover the track or text of a neighbouring hex.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Backgrounds,
+    Terrain,
+    TrackContrast,
+    Track,
+    Cities,
+    Tokens,
+    Text,
+    Grid,
+}
+
+impl Layer {
+    /// All layers in the order that they are drawn
+    pub const ALL: [Layer; 8] = [Layer::Backgrounds, Layer::Terrain,
+                                 Layer::TrackContrast, Layer::Track,
+                                 Layer::Cities, Layer::Tokens, Layer::Text,
+                                 Layer::Grid];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Layer::Backgrounds => "backgrounds",
+            Layer::Terrain => "terrain",
+            Layer::TrackContrast => "track-contrast",
+            Layer::Track => "track",
+            Layer::Cities => "cities",
+            Layer::Tokens => "tokens",
+            Layer::Text => "text",
+            Layer::Grid => "grid",
+        }
+    }
+}
+
+impl str::FromStr for Layer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layer::ALL.iter()
+            .find(|layer| layer.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("Unknown layer '{}'", s))
+    }
+}
+
+/// Tiles that are drawn once as a symbol and then placed with `<use>`
+///
+/// Most hexes on a map look exactly the same, so this keeps the files small.
+struct TileSymbols {
+    /// Number of the symbol for each drawing key, `None` when there is
+    /// nothing to draw
+    ids: HashMap<String, Option<usize>>,
+    symbols: Vec<node::element::Symbol>,
+}
+
+impl TileSymbols {
+    fn new() -> TileSymbols {
+        TileSymbols {
+            ids: HashMap::new(),
+            symbols: vec![],
+        }
+    }
+
+    /// Place a tile with its center at `pos`, the tile is only drawn when
+    /// no tile that looks the same was placed before
+    fn place(&mut self, tile: &dyn tile::TileSpec, pos: &Vector2<f64>,
+             map: &game::Map) -> node::element::Use {
+        let id = self.symbol(tile.drawing_key(), || {
+            Some(draw_tile(tile, &Vector2::new(0.0, 0.0), map))
+        });
+        symbol_use(id.unwrap(), tile, pos, map)
+    }
+
+    /// Place the parts of a tile that belong in a layer, `None` when the
+    /// tile has nothing in that layer
+    fn place_layer(&mut self, tile: &dyn tile::TileSpec, pos: &Vector2<f64>,
+                   map: &game::Map, layer: Layer)
+            -> Option<node::element::Use> {
+        let key = tile.drawing_key()
+            .map(|key| format!("{}#{}", key, layer.name()));
+        self.symbol(key, || {
+            draw_tile_layer(tile, &Vector2::new(0.0, 0.0), map, layer)
+        }).map(|id| symbol_use(id, tile, pos, map))
+    }
+
+    /// Number of the symbol with the drawing of `key`, `draw` is only
+    /// called for keys that weren't seen before
+    fn symbol<F>(&mut self, key: Option<String>, draw: F) -> Option<usize>
+            where F: FnOnce() -> Option<Group> {
+        if let Some(id) = key.as_ref().and_then(|key| self.ids.get(key)) {
+            return *id;
+        }
+        let id = draw().map(|drawing| {
+            let id = self.symbols.len();
+            self.symbols.push(node::element::Symbol::new()
+                .set("id", format!("tile-{}", id))
+                .set("overflow", "visible")
+                .add(drawing));
+            id
+        });
+        if let Some(key) = key {
+            self.ids.insert(key, id);
+        }
+        id
+    }
+
+    /// The symbols of all tiles that were placed
+    fn definitions(self) -> node::element::Definitions {
+        self.symbols.into_iter()
+            .fold(node::element::Definitions::new(), |defs, symbol| {
+                defs.add(symbol)
+            })
+    }
+}
+
+/// Refer to the symbol of a tile to draw it with its center at `pos`
+fn symbol_use(id: usize, tile: &dyn tile::TileSpec, pos: &Vector2<f64>,
+              map: &game::Map) -> node::element::Use {
+    let pos = pos * helpers::scale(map);
+    let mut tile_use = node::element::Use::new()
+        .set("xlink:href", format!("#tile-{}", id))
+        .set("x", pos.x)
+        .set("y", pos.y)
+        .set("data-orientation",
+             tile::angle_to_direction(tile.orientation()));
+    if !tile.name().is_empty() {
+        tile_use = tile_use.set("data-tile", helpers::escape(tile.name()));
+    }
+    tile_use
+}
 
 /// Draws tile definitions
 pub fn draw_tile_definitions(
@@ -42,10 +195,53 @@ pub fn draw_tile_definitions(
     g
 }
 
+/// Draws a tile in every orientation on both a horizontal and a vertical
+/// map, with the axes of hexagon-space and the positions of its text
+pub fn draw_tile_preview(tile: &dyn tile::TileSpec,
+                         icons: &HashMap<String, game::IconSymbol>)
+        -> svg::Document {
+    let mut info = game::Map::default();
+    let scale = helpers::scale(&info);
+    let mut doc = svg::Document::new()
+        .set("width", format!("{}", (2.25 * 6.0 - 0.05) * scale))
+        .set("height", format!("{}", 6.2 * scale))
+        .set("xmlns:xlink", XLINK_NS)
+        .add(draw_icon_definitions(icons));
+    let orientations = [(Orientation::Horizontal, "Horizontal"),
+                        (Orientation::Vertical, "Vertical")];
+    for (row, &(ref orientation, name)) in orientations.iter().enumerate() {
+        info.orientation = orientation.clone();
+        let top = 3.1 * row as f64;
+        doc = doc.add(element::draw_text(
+                name,
+                &(Vector2::new(0.1, top + 0.4) * scale),
+                &tile::TextAnchor::Start, Some("150%"), None));
+        for (i, direction) in tile::DIRECTIONS.iter().enumerate() {
+            let pos = Vector2::new(1.1 + 2.25 * i as f64, top + 1.9);
+            let rotation = i as f64 * PI / 3.0;
+            let placed = game::PlacedTile::new_from(tile)
+                .set_orientation(rotation);
+            let text_pos = Vector2::new(pos.x - 1.0, pos.y - 1.1) * scale;
+            doc = doc.add(draw_tile(&placed, &pos, &info))
+                .add(element::draw_axes(&pos, &info, &rotation))
+                .add(element::draw_text(direction, &text_pos,
+                                        &tile::TextAnchor::Start, None,
+                                        None));
+            for text in tile.text_spec() {
+                doc = doc.add(element::draw_text_marker(&text.position(),
+                                                        &pos, &info,
+                                                        &rotation));
+            }
+        }
+    }
+    doc
+}
+
 /// Draw a game's tile manifest
 pub fn draw_tile_manifest(game: &game::Game) -> Group {
     let mut g = Group::new();
     let mut i = 0.0;
+    let amounts = game.manifest.amounts(&game.log, &game.map);
 
     for tile in &game.manifest.tiles {
         let pos = Vector2::new(1.1_f64 + 2.25 * (i % TILES_PER_ROW),
@@ -53,14 +249,8 @@ pub fn draw_tile_manifest(game: &game::Game) -> Group {
         g = g.add(draw_tile(tile, &pos, &game.map));
         i += 1.0;
 
-        // Draw amount available
-        let amount = match game.manifest.amounts(&game.log).get(tile.name()) {
-            None => {
-                eprintln!("No tile amount found for {}", tile.name());
-                process::exit(1);
-            }
-            Some(amount) => amount.to_string(),
-        };
+        // Draw amount available, every tile has one when the game is read
+        let amount = amounts[tile.name()].to_string();
         let text_pos = helpers::scale(&game.map) *
             Vector2::new(pos.x-1.0, pos.y-0.7);
         g = g.add(element::draw_text(&format!("{}×", amount), &text_pos,
@@ -70,143 +260,315 @@ pub fn draw_tile_manifest(game: &game::Game) -> Group {
     g
 }
 
-/// Draws sheets with tiles of them for printing
-pub fn draw_tile_sheets(game: &game::Game) -> Vec<svg::Document> {
-    const TILES_PER_PAGE: u32 = 30;
-    const TILES_PER_COL: u32 = 6;
+const TILES_PER_PAGE: usize = 30;
+const TILES_PER_COL: usize = 6;
+
+/// Every copy of every tile in the manifest, in the order that they are
+/// printed
+fn sheet_tiles(game: &game::Game) -> Vec<&tile::Tile> {
+    let amounts = game.manifest.amounts(&None, &game.map);
+    game.manifest.tiles.iter()
+        .flat_map(|tile| {
+            let amount = amounts[tile.name()] as usize;
+            ::std::iter::repeat_n(tile, amount)
+        })
+        .collect()
+}
+
+/// Number of sheets that are needed to print all tiles
+pub fn tile_sheet_count(game: &game::Game) -> usize {
+    // A sheet is started after every full sheet, even when no tiles remain
+    sheet_tiles(game).len() / TILES_PER_PAGE + 1
+}
+
+/// Draws a sheet with tiles on it for printing
+pub fn draw_tile_sheet(game: &game::Game, page: usize) -> svg::Document {
     // Always draw vertical (fits more on a page)
     let mut info = game.map.clone();
     info.orientation = Orientation::Vertical;
-    let mut drawn = 0;
-    let mut sheets = vec![];
-    let mut cur_doc = svg::Document::new()
+    let mut symbols = TileSymbols::new();
+    let uses: Vec<_> = sheet_tiles(game).into_iter()
+        .skip(page * TILES_PER_PAGE)
+        .take(TILES_PER_PAGE)
+        .enumerate()
+        .map(|(drawn, tile)| {
+            let x = (drawn / TILES_PER_COL) as f64;
+            let y = (drawn % TILES_PER_COL) as f64;
+            let pos = Vector2::new(3.0_f64.sqrt() * (x + 1.0),
+                                   2.0 * y + 1.75 + (x % 2.0));
+            symbols.place(tile, &pos, &info)
+        })
+        .collect();
+    let mut doc = svg::Document::new()
         .set("width", "210mm")
         .set("height", "297mm")
+        .set("xmlns:xlink", XLINK_NS)
+        .add(draw_icon_definitions(&game.icons))
+        .add(symbols.definitions())
         .add(element::draw_text(
-                "Tile sheet 0",
-                &(Vector2::new(2.0_f64, 0.5) * helpers::scale(&info)),
+                &format!("Tile sheet {}", page),
+                &(Vector2::new(2.0, 0.5) * helpers::scale(&info)),
                 &tile::TextAnchor::Start,
                 Some("200%"), None));
-    for tile in game.manifest.tiles.iter() {
-        for _ in 0..*game.manifest.amounts(&None).get(tile.name()).unwrap() {
-            let x = ((drawn % TILES_PER_PAGE) / TILES_PER_COL) as f64;
-            let y = (drawn % TILES_PER_COL) as f64;
-            let pos = Vector2::new(3.0_f64.sqrt() * (x + 1.0),
-                                   2.0 * y + 1.75 + (x % 2.0));
-            cur_doc = cur_doc.add(draw_tile(tile, &pos, &info));
-            drawn += 1;
-            // When a sheet is full, append this to the list and start a
-            // new page
-            if drawn % TILES_PER_PAGE == 0 {
-                sheets.push(cur_doc);
-                cur_doc = svg::Document::new()
-                    .set("width", "210mm")
-                    .set("height", "297mm")
-                    .add(element::draw_text(
-                            &format!("Tile sheet {}", drawn/TILES_PER_PAGE),
-                            &(Vector2::new(2.0, 0.5) * helpers::scale(&info)),
-                            &tile::TextAnchor::Start,
-                            Some("200%"), None));
-            }
-        }
+    for tile_use in uses {
+        doc = doc.add(tile_use);
     }
-    sheets.push(cur_doc);
-    sheets
+    doc
 }
 
 /// Convert location to cube coordinate
 fn convert_coord(col: i32, row: i32, map: &game::Map) -> [f64; 3] {
+    let shift = map.coordinates.parity.shift() as i32;
     match map.orientation {
         Orientation::Vertical => {
-            let x = (col - (row - (row % 2)) / 2) as f64;
+            let x = (col - (row + shift) / 2) as f64;
             let z = row as f64;
             [x, -x-z, -z]
         }
         Orientation::Horizontal => {
             let x = col as f64;
-            let z = (row - (col - (col % 2)) / 2) as f64;
+            let z = (row - (col + shift) / 2) as f64;
             [x, -x-z, -z]
         }
     }
 }
 
+/// Position of the center of a hex on the map
+fn hex_center(location: (u32, u32),
+              offset: &Vector2<f64>,
+              map: &game::Map) -> Vector2<f64> {
+    let basis = helpers::get_basis(&map.orientation);
+    offset + basis
+        * na::Vector3::from(convert_coord(location.0 as i32,
+                                          location.1 as i32, map))
+            .component_mul(&na::Vector3::new(2.0, 1.0, 1.0))
+}
+
+/// Side of a hex that it shares with a neighbouring hex
+///
+/// Returns `None` when the hexes are not next to each other.
+pub fn neighbour_side(from: (u32, u32), to: (u32, u32),
+                      map: &game::Map) -> Option<&'static str> {
+    let origin = Vector2::new(0.0, 0.0);
+    let step = hex_center(to, &origin, map) - hex_center(from, &origin, map);
+    let basis = helpers::get_basis(&map.orientation);
+    tile::DIRECTIONS.iter()
+        .find(|direction| {
+            let edge = tile::Coordinate::Named(direction.to_string())
+                .as_vector();
+            // The neighbour is twice as far away as the middle of the edge
+            (step - basis * edge * 2.0).norm() < 1e-6
+        })
+        .cloned()
+}
+
+/// The entries of a map from locations, sorted by location so that the map
+/// is drawn the same way every time
+fn by_location<T>(map: &HashMap<(u32, u32), T>) -> Vec<(&(u32, u32), &T)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|&(location, _)| *location);
+    entries
+}
+
 /// Draw the map of a game
 pub fn draw_map(game: &game::Game, options: &super::Options) -> svg::Document {
-    let width: f64;
-    let height: f64;
-    let offset: Vector2<f64>;
-    match &game.map.orientation {
-        &Orientation::Horizontal => {
-            width = 0.3 * 3.0_f64.sqrt() + game.map.width as f64 * 1.5;
-            height = (0.5 + game.map.height as f64) * 3.0_f64.sqrt();
-            offset = Vector2::new(consts::BORDER + 1.0,
-                                  consts::BORDER + 3.0_f64.sqrt() / 2.0);
-        }
-        &Orientation::Vertical => {
-            width = (0.5 + game.map.width as f64) * 3.0_f64.sqrt();
-            height = 0.3 * 3.0_f64.sqrt() + game.map.height as f64 * 1.5;
-            offset = Vector2::new(consts::BORDER + 3.0_f64.sqrt() / 2.0,
-                                  consts::BORDER + 1.0);
-        }
-    }
+    // Determine the size of the map from the hexes that are on it, maps
+    // without hexes are rejected when they are read
+    let locations = game.map.locations();
+    let hex_size = match game.map.orientation {
+        Orientation::Horizontal => Vector2::new(1.0, 3.0_f64.sqrt() / 2.0),
+        Orientation::Vertical => Vector2::new(3.0_f64.sqrt() / 2.0, 1.0),
+    };
+    let centers: Vec<_> = locations.iter()
+        .map(|l| hex_center(*l, &Vector2::new(0.0, 0.0), &game.map))
+        .collect();
+    let min = centers.iter().skip(1).fold(centers[0], |acc, c| {
+        Vector2::new(acc.x.min(c.x), acc.y.min(c.y))
+    }) - hex_size;
+    let max = centers.iter().skip(1).fold(centers[0], |acc, c| {
+        Vector2::new(acc.x.max(c.x), acc.y.max(c.y))
+    }) + hex_size;
+    let width = max.x - min.x;
+    let height = max.y - min.y;
+    let offset = Vector2::new(consts::BORDER, consts::BORDER) - min;
     let page_width = (width + 2.0 * consts::BORDER)
         * helpers::scale(&game.map);
     let page_height = (height + 2.0 * consts::BORDER)
         * helpers::scale(&game.map);
-    let basis = helpers::get_basis(&game.map.orientation);
     let mut doc = svg::Document::new()
         .set("width", format!("{}", page_width))
-        .set("height", format!("{}", page_height));
+        .set("height", format!("{}", page_height))
+        .set("xmlns:xlink", XLINK_NS)
+        .add(draw_icon_definitions(&game.icons));
 
-    // Draw tiles
-    let placed = game.placed_tiles();
+    let replay = game.replay();
     let tiles = game.map.tiles();
-    let tiles = game::top_tiles(&placed, &tiles);
-    for (&(x, y), tile) in tiles.iter() {
-        let pos = offset + basis
-            * na::Vector3::from(convert_coord(x as i32, y as i32, &game.map))
-                .component_mul(&na::Vector3::new(2.0, 1.0, 1.0));
-        doc = doc.add(draw_tile(tile.deref(), &pos, &game.map));
+    let tiles = game::top_tiles(&replay.placed, &tiles);
+    let mut symbols = TileSymbols::new();
+    // Hexes of an area are outlined together
+    let area_hexes: Vec<(u32, u32)> = game.map.areas.iter()
+        .flat_map(|area| area.locations.iter())
+        .map(|location| location.as_coord(&game.map))
+        .collect();
+    let mut layers = vec![];
+    for layer in Layer::ALL.iter() {
+        if options.hidden_layers.contains(layer) {
+            continue;
+        }
+        let mut g = Group::new().set("id", format!("layer-{}", layer.name()));
+
+        // Fill the space around the hexes
+        if let (Layer::Backgrounds, Some(background)) =
+                (layer, &game.map.background) {
+            g = g.add(svg::node::element::Rectangle::new()
+                .set("x", consts::BORDER * helpers::scale(&game.map))
+                .set("y", consts::BORDER * helpers::scale(&game.map))
+                .set("width", width * helpers::scale(&game.map))
+                .set("height", height * helpers::scale(&game.map))
+                .set("fill", tile::colors::name_to_color(background).value()));
+        }
+
+        // Draw the part of each tile that is in this layer
+        for (location, tile) in by_location(&tiles) {
+            if *layer == Layer::Grid && area_hexes.contains(location) {
+                continue;
+            }
+            let pos = hex_center(*location, &offset, &game.map);
+            let name = game::Location::from_coord(*location, &game.map);
+            if let Some(mut tile_use) = symbols.place_layer(
+                    *tile, &pos, &game.map, *layer) {
+                tile_use = tile_use.set("data-location", name.to_string());
+                if *layer == Layer::Backgrounds {
+                    tile_use = tile_use.set("id", format!("hex-{}", name));
+                }
+                g = g.add(tile_use);
+            }
+        }
+
+        match *layer {
+            Layer::Terrain => {
+                // Draw decorations on the sides of hexes
+                for (location, decoration) in game.map.edges() {
+                    let pos = hex_center(location, &offset, &game.map);
+                    g = g.add(element::draw_edge_decoration(decoration, &pos,
+                                                            &game.map));
+                }
+            }
+            Layer::Tokens => {
+                g = g.add(draw_tokens(&replay.tokens, &tiles, &offset,
+                                      &game.map));
+            }
+            Layer::Text => {
+                for area in game.map.areas.iter() {
+                    let centers = area_centers(area, &offset, &game.map);
+                    g = g.add(element::draw_area_label(area, &centers,
+                                                       &game.map));
+                }
+            }
+            Layer::Grid => {
+                for area in game.map.areas.iter() {
+                    let sides = area_sides(area, &offset, &game.map);
+                    g = g.add(element::draw_area(area, &sides, &game.map));
+                }
+                for barrier in game.map.barriers.iter() {
+                    let pos = hex_center(barrier.location.as_coord(&game.map),
+                                         &offset, &game.map);
+                    g = g.add(element::draw_barrier(barrier, &pos,
+                                                    &game.map));
+                }
+                g = g.add(element::draw_coordinate_system(
+                        game, options, &locations, &offset, width, height));
+            }
+            _ => {}
+        }
+        layers.push(g);
     }
 
-    // Draw barriers
-    for barrier in game.map.barriers.iter() {
-        let (x, y) = barrier.location.as_coord(&game.map.orientation);
-        let pos = offset + basis
-            * na::Vector3::from(convert_coord(x as i32, y as i32, &game.map))
-                .component_mul(&na::Vector3::new(2.0, 1.0, 1.0));
-        doc = doc.add(element::draw_barrier(barrier, &pos, &game.map));
+    // The symbols that the tiles use come first
+    doc = doc.add(symbols.definitions());
+    for layer in layers {
+        doc = doc.add(layer);
     }
+    doc
+}
+
+/// Centers of the hexes of an area that spans multiple hexes
+fn area_centers(area: &game::Area, offset: &Vector2<f64>,
+                map: &game::Map) -> Vec<Vector2<f64>> {
+    area.locations.iter()
+        .map(|l| hex_center(l.as_coord(map), offset, map))
+        .collect()
+}
+
+/// Sides of the hexes of an area that are not shared with another hex of the
+/// area, together with the center of their hex
+fn area_sides(area: &game::Area, offset: &Vector2<f64>,
+              map: &game::Map) -> Vec<(Vector2<f64>, &'static str)> {
+    let locations: Vec<_> = area.locations.iter()
+        .map(|l| l.as_coord(map))
+        .collect();
+    let mut sides = vec![];
+    for &location in locations.iter() {
+        let center = hex_center(location, offset, map);
+        for side in tile::DIRECTIONS.iter() {
+            let shared = locations.iter().any(|&other| {
+                neighbour_side(location, other, map) == Some(side)
+            });
+            if !shared {
+                sides.push((center, *side));
+            }
+        }
+    }
+    sides
+}
 
-    // Draw tokens
-    for (location, tokens) in game.tokens().iter() {
-        let tile = tiles.get(&location).unwrap();
+/// Draw the tokens on a map
+fn draw_tokens(tokens: &HashMap<(u32, u32), Vec<game::Token>>,
+               tiles: &HashMap<(u32, u32), &dyn tile::TileSpec>,
+               offset: &Vector2<f64>,
+               map: &game::Map) -> Group {
+    let basis = helpers::get_basis(&map.orientation);
+    let mut g = Group::new();
+    for (location, tokens) in by_location(tokens) {
+        let mut tokens: Vec<_> = tokens.iter().collect();
+        tokens.sort_by_key(|token| (token.station, token.circle));
+        // Tokens are left without a city when they are on a hex without a
+        // tile or when a tile with fewer cities is laid
+        let tile = match tiles.get(location) {
+            Some(tile) => tile,
+            None => continue,
+        };
         let rot = helpers::rotate(&tile.orientation());
+        let name = game::Location::from_coord(*location, map);
         for token in tokens {
-            let mut g = Group::new();
-            let city = tile.cities().get(token.station).unwrap().clone();
-            let center = offset + basis
-                * na::Vector3::from(convert_coord(location.0 as i32,
-                                                  location.1 as i32,
-                                                  &game.map))
-                    .component_mul(&na::Vector3::new(2.0, 1.0, 1.0));
+            let mut token_g = Group::new()
+                .set("data-company", helpers::escape(&token.name))
+                .set("data-location", name.to_string())
+                .set("data-station", token.station);
+            let city = match tile.cities().get(token.station) {
+                Some(city) if token.circle < city.circles.max(1) =>
+                    city.clone(),
+                _ => continue,
+            };
+            let center = hex_center(*location, offset, map);
             let token_pos = helpers::city_circle_pos(&city, token.circle,
-                                                     &center, &game.map,
+                                                     &center, map,
                                                      &tile.orientation());
-            if let Orientation::Vertical = game.map.orientation {
+            if let Orientation::Vertical = map.orientation {
                 let station_pos = (center + rot * basis * city.position())
-                    * helpers::scale(&game.map);
-                g = g.set("transform", format!("rotate(-30 {} {})",
-                                               station_pos.x, station_pos.y));
+                    * helpers::scale(map);
+                token_g = token_g.set("transform",
+                                      format!("rotate(-30 {} {})",
+                                              station_pos.x, station_pos.y));
             }
-            g = g.add(element::draw_token(&token.name, &token.color,
-                                          token.is_home,
-                                          &token_pos, &game.map));
-            doc = doc.add(g);
+            token_g = token_g.add(element::draw_token(&token.name,
+                                                      &token.color,
+                                                      token.is_home,
+                                                      &token_pos, map));
+            g = g.add(token_g);
         }
     }
-
-    doc.add(element::draw_coordinate_system(game, options, width, height))
+    g
 }
 
 /// Draws a single tile
@@ -214,72 +576,129 @@ pub fn draw_tile(tile: &tile::TileSpec,
                  pos: &Vector2<f64>,
                  map: &game::Map) -> Group
 {
-    let mut g = Group::new();
-    let basis = helpers::get_basis(&map.orientation);
-    let rotation = helpers::rotate(&tile.orientation());
-
-    g = g.add(helpers::draw_hex_background(*pos, &map, tile.color()));
-
-    // Draw white contrast lines first
-    for path in tile.paths() {
-        g = g.add(element::draw_path_contrast(&path, pos, &map,
-                                              &tile.orientation()));
-    }
-    for city in tile.cities() {
-        g = g.add(element::draw_city_contrast(city, pos, &map,
-                                              &tile.orientation()));
-    };
-
-    // Draw terrain
-    if let Some(terrain) = tile.terrain() {
-        g = g.add(element::draw_terrain(&terrain, pos, &map));
+    let mut g = Group::new()
+        .set("data-orientation",
+             tile::angle_to_direction(tile.orientation()));
+    if !tile.name().is_empty() {
+        g = g.set("data-tile", helpers::escape(tile.name()));
     }
-
-    // Draw elements
-    if tile.is_lawson() {
-        g = g.add(element::draw_lawson(*pos, &map));
-    }
-    for path in tile.paths() {
-        g = g.add(element::draw_path(&path, pos, &map, &tile.orientation()));
-    };
-
-    for stop in tile.stops() {
-        g = g.add(element::draw_stop(stop, *pos, &map, tile,
-                                     &tile.orientation()));
-    }
-
-    for city in tile.cities() {
-        g = g.add(element::draw_city(city, *pos, &map, tile,
-                                     &tile.orientation()));
+    for layer in Layer::ALL.iter() {
+        if let Some(drawing) = draw_tile_layer(tile, pos, map, *layer) {
+            g = g.add(drawing);
+        }
     }
+    g
+}
 
-    for arrow in tile.arrows() {
-        g = g.add(element::draw_arrow(&arrow, pos, &map));
-    }
+/// Draws the parts of a tile that belong in a layer, `None` when the tile
+/// has nothing in that layer
+pub fn draw_tile_layer(tile: &dyn tile::TileSpec,
+                       pos: &Vector2<f64>,
+                       map: &game::Map,
+                       layer: Layer) -> Option<Group>
+{
+    let basis = helpers::get_basis(&map.orientation);
+    let rotation = helpers::rotate(&tile.orientation());
+    let mut g = Group::new();
 
-    // Draw text on tile
-    for text in tile.text_spec() {
-        let text_pos = helpers::scale(&map) *
-            (rotation * basis * text.position() + pos);
-        let mut t = element::draw_text(&tile.get_text(&text.id), &text_pos,
-                                       &text.anchor, text.size(), text.weight);
-        // Rotate the tile number with the orientation of the map
-        if let Orientation::Vertical = map.orientation {
-            let mut angle = -30;
-            if text.id == "number" {
-                angle += (tile.orientation() * 180.0 / PI) as i32;
+    match layer {
+        Layer::Backgrounds => {
+            g = g.add(helpers::draw_hex_background(*pos, map, tile.color()));
+        }
+        Layer::Terrain => {
+            let terrain = tile.terrain();
+            let icons = tile.icons();
+            if terrain.is_empty() && icons.is_empty() {
+                return None;
+            }
+            for terrain in terrain {
+                g = g.add(element::draw_terrain(&terrain, pos, map));
+            }
+            for icon in icons {
+                g = g.add(element::draw_icon(&icon, pos, map,
+                                             &tile.orientation()));
             }
-            t = t.set("transform",
-                      format!("rotate({} {} {})", angle, text_pos.x, text_pos.y));
         }
-        g = g.add(t);
-    }
-
-    // Draw revenue track
-    if let Some(track) = tile.revenue_track() {
-        g = g.add(element::draw_revenue_track(&track, pos, &map));
+        Layer::TrackContrast => {
+            // White lines that separate track from what is below it
+            let paths = tile.paths();
+            let cities = tile.cities();
+            if paths.is_empty() && cities.is_empty() {
+                return None;
+            }
+            for path in paths {
+                g = g.add(element::draw_path_contrast(&path, pos, map,
+                                                      &tile.orientation()));
+            }
+            for city in cities {
+                g = g.add(element::draw_city_contrast(city, pos, map,
+                                                      &tile.orientation()));
+            }
+        }
+        Layer::Track => {
+            let paths = tile.paths();
+            let arrows = tile.arrows();
+            if !tile.is_lawson() && paths.is_empty() && arrows.is_empty() {
+                return None;
+            }
+            if tile.is_lawson() {
+                g = g.add(element::draw_lawson(*pos, map));
+            }
+            for path in paths {
+                g = g.add(element::draw_path(&path, pos, map,
+                                             &tile.orientation()));
+            }
+            for arrow in arrows {
+                g = g.add(element::draw_arrow(&arrow, pos, map));
+            }
+        }
+        Layer::Cities => {
+            let stops = tile.stops();
+            let cities = tile.cities();
+            if stops.is_empty() && cities.is_empty() {
+                return None;
+            }
+            for stop in stops {
+                g = g.add(element::draw_stop(stop, *pos, map, tile,
+                                             &tile.orientation()));
+            }
+            for city in cities {
+                g = g.add(element::draw_city(city, *pos, map, tile,
+                                             &tile.orientation()));
+            }
+        }
+        // Tokens are only placed on a map
+        Layer::Tokens => return None,
+        Layer::Text => {
+            let texts = tile.text_spec();
+            let revenue_track = tile.revenue_track();
+            if texts.is_empty() && revenue_track.is_none() {
+                return None;
+            }
+            for text in texts {
+                let text_pos = helpers::scale(map) *
+                    (rotation * basis * text.position() + pos);
+                let mut t = element::draw_text(tile.get_text(&text.id),
+                                               &text_pos, &text.anchor,
+                                               text.size(), text.weight);
+                // Rotate the tile number with the orientation of the map
+                if let Orientation::Vertical = map.orientation {
+                    let mut angle = -30;
+                    if text.id == "number" {
+                        angle += (tile.orientation() * 180.0 / PI) as i32;
+                    }
+                    t = t.set("transform", format!("rotate({} {} {})", angle,
+                                                   text_pos.x, text_pos.y));
+                }
+                g = g.add(t);
+            }
+            if let Some(track) = revenue_track {
+                g = g.add(element::draw_revenue_track(&track, pos, map));
+            }
+        }
+        Layer::Grid => {
+            g = g.add(helpers::draw_hex_edge(*pos, map));
+        }
     }
-
-    // Draw outline last to prevent visual effects
-    g.add(helpers::draw_hex_edge(*pos, &map))
+    Some(g)
 }
@@ -1,16 +1,22 @@
 extern crate nalgebra as na;
+extern crate serde_json;
 extern crate serde_yaml;
 
-use std::collections::HashMap;
+use std::char;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
 use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
 use std::process;
 
+use schema;
 use tile;
 use tile::TileSpec;
 
 /// Orientation that hexes should be in
-#[derive(Clone,Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub enum Orientation {
     /// Hexes should have a flat top
     Horizontal,
@@ -27,47 +33,257 @@ pub enum Location {
 }
 
 impl Location {
-    pub fn as_coord(&self, orientation: &Orientation) -> (u32, u32) {
+    /// Coordinate of a location that is known to be valid
+    ///
+    /// # Panics
+    ///
+    /// On invalid names, the locations in maps, companies and logs are
+    /// checked when they are read
+    pub fn as_coord(&self, map: &Map) -> (u32, u32) {
+        self.to_coord(map).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Coordinate of the location, or an error for invalid names
+    pub fn to_coord(&self, map: &Map) -> Result<(u32, u32), String> {
         match *self {
-            Location::Coord(x, y) => (x, y),
-            Location::Named(ref s) => {
-                // Convert letter(s) to number
-                let a = s.chars().take_while(|c| c.is_alphabetic())
-                    .map(|c| c.to_digit(36).unwrap() - 9)
-                    .fold(0, |acc, d| 26 * acc + d) - 1;
-                // Map 1 based counting in string to 0 based
-                let b = (s.chars().skip_while(|c| c.is_alphabetic())
-                    .collect::<String>().parse::<u32>().unwrap() - 1) / 2;
-                match *orientation {
-                    Orientation::Horizontal => (a, b),
-                    Orientation::Vertical => (b, a),
-                }
-            }
+            Location::Coord(x, y) => Ok((x, y)),
+            Location::Named(ref s) => Location::parse(s, map),
+        }
+    }
+
+    /// Converts a name such as "G19" to a coordinate on a map
+    pub fn parse(name: &str, map: &Map) -> Result<(u32, u32), String> {
+        let coords = &map.coordinates;
+        let letters: String = name.chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+        let digits = &name[letters.len()..];
+        if letters.is_empty() || digits.is_empty() ||
+                !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("Invalid location '{}': expected letters \
+                                followed by a number, such as 'G19'", name));
+        }
+        if letters.chars().any(|c| c.is_ascii_lowercase()) {
+            return Err(format!("Invalid location '{}': letters must be \
+                                upper case", name));
+        }
+        let letter = letter_to_number(&letters).map_err(|err| {
+            format!("Invalid location '{}': {}", name, err)
+        })?;
+        let number = digits.parse::<u32>().map_err(|err| {
+            format!("Invalid location '{}': {}", name, err)
+        })?;
+        let first_letter = coords.first_letter_number();
+        if letter < first_letter || number < coords.first_number {
+            return Err(format!("Invalid location '{}': the first hex is at \
+                                letter {} and number {}", name,
+                               coords.first_letter, coords.first_number));
+        }
+        let (letter, number) = (letter - first_letter,
+                                number - coords.first_number);
+        let (column, row) = match coords.letters(&map.orientation) {
+            Axis::Columns => (letter, number),
+            Axis::Rows => (number, letter),
+        };
+        // Numbering along the staggered direction goes in steps of half a
+        // hex, so only every other number is used within a row or column
+        let location = match map.orientation {
+            Orientation::Horizontal => (column, row / 2),
+            Orientation::Vertical => (column / 2, row),
+        };
+        let expected = Location::from_coord(location, map);
+        if expected.to_string() != name {
+            return Err(format!("Invalid location '{}': no hex has this \
+                                name, did you mean '{}'?", name, expected));
+        }
+        Ok(location)
+    }
+
+    /// Name of a coordinate on a map, such as "G19"
+    pub fn from_coord(location: (u32, u32), map: &Map) -> Location {
+        let coords = &map.coordinates;
+        let shift = coords.parity.shift();
+        let (x, y) = location;
+        let (column, row) = match map.orientation {
+            Orientation::Horizontal => (x, 2 * y + (x + shift) % 2),
+            Orientation::Vertical => (2 * x + (y + shift) % 2, y),
+        };
+        let (letter, number) = match coords.letters(&map.orientation) {
+            Axis::Columns => (column, row),
+            Axis::Rows => (row, column),
+        };
+        Location::Named(format!("{}{}",
+            number_to_letter(letter + coords.first_letter_number()),
+            number + coords.first_number))
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Location::Coord(x, y) => write!(f, "[{}, {}]", x, y),
+            Location::Named(ref s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Converts a number to a coordinate letter 'A', 'B', .. 'AA', 'AB' etc.
+pub fn number_to_letter(n: u32) -> String {
+    match n {
+        n if n < 26 =>
+            char::from_digit(n + 10, 36).unwrap().to_string().to_uppercase(),
+        n => number_to_letter(n / 26 - 1) + &number_to_letter(n % 26),
+    }
+}
+
+/// Converts a coordinate letter 'A', 'B', .. 'AA', 'AB' etc. to a number
+///
+/// Lower case letters count the same as upper case letters, see
+/// `Location::parse` for the checks on user input.
+pub fn letter_to_number(s: &str) -> Result<u32, String> {
+    let number = s.chars().try_fold(0u32, |acc, c| {
+        let digit = c.to_digit(36).filter(|&d| d >= 10)
+            .ok_or_else(|| format!("'{}' is not a letter", c))? - 9;
+        acc.checked_mul(26).and_then(|acc| acc.checked_add(digit))
+            .ok_or_else(|| format!("'{}' has too many letters", s))
+    })?;
+    number.checked_sub(1).ok_or_else(|| String::from("no letters given"))
+}
+
+/// Direction on the map that is labelled by part of a coordinate
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum Axis {
+    Columns,
+    Rows,
+}
+
+/// Which of the staggered columns or rows are shifted by half a hex
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum Parity {
+    /// The second, fourth, etc. column or row is shifted away from the edge
+    #[default]
+    Odd,
+    /// The first, third, etc. column or row is shifted away from the edge
+    Even,
+}
+
+impl Parity {
+    /// Amount that is added to a column or row before checking if it is odd
+    pub fn shift(&self) -> u32 {
+        match *self {
+            Parity::Odd => 0,
+            Parity::Even => 1,
         }
     }
 }
 
-#[derive(Clone, Deserialize)]
+/// How the hexes on a map are named, e.g. "G19"
+///
+/// Names consist of letters followed by a number. One of them counts whole
+/// columns or rows, the other counts in steps of half a hex along the
+/// staggered direction of the map.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Coordinates {
+    /// Axis labelled by letters, the columns for horizontal maps and the
+    /// rows for vertical maps by default
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub letters: Option<Axis>,
+    #[serde(default)]
+    pub parity: Parity,
+    /// Letter used for the first column or row
+    #[serde(default="Coordinates::default_first_letter")]
+    pub first_letter: String,
+    /// Number used for the first column or row
+    #[serde(default="Coordinates::default_first_number")]
+    pub first_number: u32,
+}
+
+impl Coordinates {
+    fn default_first_letter() -> String {
+        String::from("A")
+    }
+
+    fn default_first_number() -> u32 {
+        1
+    }
+
+    /// Number of the first letter, which is checked when the map is read
+    pub fn first_letter_number(&self) -> u32 {
+        letter_to_number(&self.first_letter)
+            .expect("The first letter of the coordinates is invalid")
+    }
+
+    /// Axis that is labelled by letters
+    pub fn letters(&self, orientation: &Orientation) -> Axis {
+        match (self.letters, orientation) {
+            (Some(axis), _) => axis,
+            (None, &Orientation::Horizontal) => Axis::Columns,
+            (None, &Orientation::Vertical) => Axis::Rows,
+        }
+    }
+
+    /// Label of a column or row along an axis
+    pub fn label(&self, n: u32, axis: Axis,
+                 orientation: &Orientation) -> String {
+        if axis == self.letters(orientation) {
+            number_to_letter(n + self.first_letter_number())
+        } else {
+            (n + self.first_number).to_string()
+        }
+    }
+}
+
+impl Default for Coordinates {
+    fn default() -> Coordinates {
+        Coordinates {
+            letters: None,
+            parity: Parity::default(),
+            first_letter: Coordinates::default_first_letter(),
+            first_number: Coordinates::default_first_number(),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Map {
+    #[serde(default="schema::unmarked")]
+    pub version: u32,
     pub orientation: Orientation,
     pub scale: f64,
-    pub width: u32,
-    pub height: u32,
-    #[serde(rename="tiles")]
-    raw_tiles: Vec<MapTile>,
+    /// Number of columns, when omitted the hexes determine the map size
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub width: Option<u32>,
+    /// Number of rows, when omitted the hexes determine the map size
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub height: Option<u32>,
+    /// Color of the area around and between the hexes
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub background: Option<String>,
     #[serde(default)]
+    pub coordinates: Coordinates,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
     pub barriers: Vec<Barrier>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub areas: Vec<Area>,
+    #[serde(rename="tiles")]
+    raw_tiles: Vec<MapTile>,
 }
 
 impl Default for Map {
     fn default() -> Map {
         Map {
+            version: schema::VERSION,
             orientation: Orientation::Horizontal,
             scale: 3.81, // Hexes are usually 3.81cm flat-to-flat
-            width: 5,
-            height: 5,
+            width: None,
+            height: None,
+            background: None,
+            coordinates: Coordinates::default(),
             raw_tiles: vec![],
             barriers: vec![],
+            areas: vec![],
         }
     }
 }
@@ -75,47 +291,162 @@ impl Default for Map {
 impl Map {
     pub fn load(dir: PathBuf,
                 definitions: &HashMap<String, tile::TileDefinition>) -> Map {
-        let mut map: Map;
+        Map::read(dir, definitions).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    }
+
+    /// Read the map of a game and connect its tiles to their definitions
+    pub fn read(dir: PathBuf,
+                definitions: &HashMap<String, tile::TileDefinition>)
+            -> Result<Map, String> {
         let map_filename = dir.join("map.yaml");
         if !dir.exists() {
-            eprintln!("Can't find a game in {}", dir.to_string_lossy());
-            process::exit(1);
+            return Err(format!("Can't find a game in {}",
+                               dir.to_string_lossy()));
         }
 
         println!("Reading map information...");
-        match File::open(map_filename) {
-            Err(e) => {
-                eprintln!("Couldn't open map file: {}", e);
-                process::exit(1);
-            }
-            Ok(file) => {
-                map = serde_yaml::from_reader(file).unwrap_or_else(|err| {
-                    eprintln!("Failed to parse map: {}", err);
-                    process::exit(1);
-                });
+        let text = fs::read_to_string(&map_filename).map_err(|e| {
+            format!("Couldn't open map file: {}", e)
+        })?;
+        schema::check_yaml(schema::Kind::Map, &text,
+                           &map_filename.to_string_lossy())?;
+        let mut map: Map = serde_yaml::from_str(&text).map_err(|err| {
+            format!("Failed to parse map: {}", err)
+        })?;
+        map.version = schema::VERSION;
+        let first_letter = &map.coordinates.first_letter;
+        if first_letter.is_empty() ||
+                !first_letter.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("The first letter of the coordinates must \
+                               consist of upper case letters, not '{}'",
+                               first_letter));
+        }
+        letter_to_number(first_letter).map_err(|err| {
+            format!("Invalid first letter of the coordinates: {}", err)
+        })?;
+        for area in map.areas.iter() {
+            if area.locations.is_empty() {
+                return Err(format!("Area '{}' does not contain any \
+                                   locations", area.name));
             }
-        };
+        }
+        map.check_locations()?;
+        if map.locations().is_empty() {
+            return Err(String::from("The map does not contain any hexes"));
+        }
         // Connect the tiles to their definitions
         for tile in map.raw_tiles.iter_mut() {
             let base = tile.tile.clone();
             match definitions.get(&base) {
                 Some(def) => tile.set_definition(def),
                 None => {
-                    eprintln!("Invalid tile for location {:?}: Unknown tile \
-                              definition '{}'", &tile.location, &tile.tile);
-                    process::exit(1);
+                    return Err(format!("Invalid tile for location {}: \
+                                       Unknown tile definition '{}'",
+                                       &tile.location, &tile.tile));
                 }
             }
         }
-        map
+        Ok(map)
+    }
+
+    /// Check every location, direction and side on the map, so that
+    /// drawing the map can't fail on them
+    fn check_locations(&self) -> Result<(), String> {
+        for tile in self.raw_tiles.iter() {
+            let location = tile.location.to_coord(self)?;
+            let name = Location::from_coord(location, self);
+            if let Some(ref orientation) = tile.orientation {
+                tile::check_direction(orientation).map_err(|err| {
+                    format!("Invalid orientation of {}: {}", name, err)
+                })?;
+            }
+            for edge in tile.edges.iter() {
+                tile::check_direction(&edge.side).map_err(|err| {
+                    format!("Invalid side of an edge of {}: {}", name, err)
+                })?;
+            }
+        }
+        for barrier in self.barriers.iter() {
+            let location = barrier.location.to_coord(self)?;
+            tile::check_direction(&barrier.side).map_err(|err| {
+                format!("Invalid side of the barrier in {}: {}",
+                        Location::from_coord(location, self), err)
+            })?;
+        }
+        for area in self.areas.iter() {
+            for location in area.locations.iter() {
+                location.to_coord(self).map_err(|err| {
+                    format!("Invalid location in area '{}': {}", area.name,
+                            err)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_tile(&mut self, tile: MapTile) {
+        self.raw_tiles.push(tile);
     }
 
     pub fn tiles(&self) -> HashMap<(u32, u32), &tile::TileSpec> {
         self.raw_tiles.iter()
-            .map(|t| (t.location.as_coord(&self.orientation),
+            .map(|t| (t.location.as_coord(self),
                       t as &tile::TileSpec))
             .collect()
     }
+
+    /// All the locations on the map
+    ///
+    /// This includes every location that has a tile. When both the width and
+    /// height of the map are given then it also includes every location
+    /// within those bounds.
+    pub fn locations(&self) -> Vec<(u32, u32)> {
+        let mut locations: Vec<(u32, u32)> = self.raw_tiles.iter()
+            .map(|t| t.location.as_coord(self))
+            .collect();
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            for x in 0..width {
+                for y in 0..height {
+                    locations.push((x, y));
+                }
+            }
+        }
+        locations.sort();
+        locations.dedup();
+        locations
+    }
+
+    /// The area that a location is part of
+    pub fn area(&self, location: (u32, u32)) -> Option<&Area> {
+        self.areas.iter().find(|area| {
+            area.locations.iter()
+                .any(|l| l.as_coord(self) == location)
+        })
+    }
+
+    /// Location that is used when calculating revenue
+    ///
+    /// All hexes in an area are the same location for revenue purposes, so
+    /// they all map to the first hex of the area.
+    pub fn revenue_location(&self, location: (u32, u32)) -> (u32, u32) {
+        match self.area(location) {
+            Some(area) => area.locations[0].as_coord(self),
+            None => location,
+        }
+    }
+
+    /// Decorations on the sides of hexes together with their location
+    pub fn edges(&self) -> Vec<((u32, u32), &EdgeDecoration)> {
+        self.raw_tiles.iter()
+            .flat_map(|t| {
+                let location = t.location.as_coord(self);
+                t.edges.iter().map(move |e| (location, e))
+            })
+            .collect()
+    }
 }
 
 /// A collection of tile specificiations
@@ -124,6 +455,7 @@ pub struct Game {
     pub map: Map,
     pub log: Option<Log>,
     pub companies: HashMap<String, Company>,
+    pub icons: HashMap<String, IconSymbol>,
 }
 
 impl Game {
@@ -133,64 +465,85 @@ impl Game {
             map: Map::default(),
             log: None,
             companies: HashMap::new(),
+            icons: HashMap::new(),
         }
     }
 
     pub fn load(dir: PathBuf,
                 definitions: &HashMap<String, tile::TileDefinition>) -> Game {
+        Game::read(dir, definitions).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    }
+
+    /// Read the manifest, companies, icons and map of a game
+    pub fn read(dir: PathBuf,
+                definitions: &HashMap<String, tile::TileDefinition>)
+            -> Result<Game, String> {
         let mut game = Game::new();
         let manifest_filename = dir.join("manifest.yaml");
         let companies_filename = dir.join("companies.yaml");
         if !dir.exists() {
-            eprintln!("Can't find a game in {}", dir.to_string_lossy());
-            process::exit(1);
+            return Err(format!("Can't find a game in {}",
+                               dir.to_string_lossy()));
         }
 
         println!("Reading tile manifest...");
-        match File::open(manifest_filename) {
-            Err(e) => {
-                eprintln!("Couldn't open manifest file: {}", e);
-                process::exit(1);
-            }
-            Ok(file) => {
-                game.manifest = serde_yaml::from_reader(file)
-                    .unwrap_or_else(|err| {
-                        eprintln!("Failed to parse manifest: {}", err);
-                        process::exit(1);
-                });
-            }
-        };
+        let text = fs::read_to_string(&manifest_filename).map_err(|e| {
+            format!("Couldn't open manifest file: {}", e)
+        })?;
+        schema::check_yaml(schema::Kind::Manifest, &text,
+                           &manifest_filename.to_string_lossy())?;
+        game.manifest = serde_yaml::from_str(&text).map_err(|err| {
+            format!("Failed to parse manifest: {}", err)
+        })?;
+        game.manifest.version = schema::VERSION;
         // Connect the manifest to the tile definitions
         for tile in game.manifest.tiles.iter_mut() {
             let base = tile.base_tile();
             match definitions.get(&base) {
                 Some(def) => tile.set_definition(def),
-                None => {
-                    eprintln!("Unknown base_tile '{}'", base);
-                    process::exit(1);
-                }
+                None => return Err(format!("Unknown base_tile '{}'", base)),
             }
         }
 
         println!("Reading companies...");
-        match File::open(companies_filename) {
-            Err(e) => {
-                eprintln!("Couldn't open company definitions: {}", e);
-                process::exit(1);
-            }
-            Ok(file) => {
-                game.companies = serde_yaml::from_reader(file)
-                    .unwrap_or_else(|err| {
-                        eprintln!("Failed to parse companies: {}", err);
-                        process::exit(1);
-                    });
-            }
+        let file = File::open(companies_filename).map_err(|e| {
+            format!("Couldn't open company definitions: {}", e)
+        })?;
+        game.companies = serde_yaml::from_reader(file).map_err(|err| {
+            format!("Failed to parse companies: {}", err)
+        })?;
+
+        // Icons are optional, only read them when the game has any
+        let icons_dir = dir.join("icons");
+        if icons_dir.exists() {
+            println!("Reading icons...");
+            game.icons = IconSymbol::read_all(icons_dir)?;
         }
 
         // Load the map itself
-        game.map = Map::load(dir, definitions);
+        game.map = Map::read(dir, definitions)?;
 
-        game
+        for tile in game.manifest.tiles.iter() {
+            if !game.manifest.amounts.contains_key(tile.name()) {
+                return Err(format!("No tile amount found for {}",
+                                   tile.name()));
+            }
+        }
+        for (name, company) in game.companies.iter() {
+            let home = match company.home {
+                Some(Home::PositionOnly(ref home)) => home,
+                Some(Home::PositionStation(ref home, _)) => home,
+                None => continue,
+            };
+            home.to_coord(&game.map).map_err(|err| {
+                format!("Invalid home of {}: {}", name, err)
+            })?;
+        }
+
+        Ok(game)
     }
 
     pub fn set_log(mut self, log: Log) -> Self {
@@ -198,15 +551,119 @@ impl Game {
         self
     }
 
+    /// Check the locations and orientations in the log, so that replaying
+    /// it can't fail on them
+    pub fn check_log(&self) -> Result<(), String> {
+        let log = match self.log {
+            Some(ref log) => log,
+            None => return Ok(()),
+        };
+        for (i, action) in log.log.iter().enumerate() {
+            let checked = match *action {
+                Action::TileLay { ref location, ref orientation, .. } =>
+                    location.to_coord(&self.map)
+                        .and_then(|_| tile::check_direction(orientation)),
+                Action::Token { ref location, .. } =>
+                    location.to_coord(&self.map).map(|_| ()),
+                Action::RemoveCompany { .. } => Ok(()),
+            };
+            checked.map_err(|err| {
+                format!("Invalid action {} in the log: {}", i + 1, err)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether an action can be appended to the log of the game
+    ///
+    /// Upgrade paths are not checked, only whether the action refers to
+    /// things that exist and fit on the map.
+    pub fn validate(&self, action: &Action) -> Result<(), String> {
+        match *action {
+            Action::TileLay { ref location, ref tile, ref orientation } => {
+                self.hex(location)?;
+                if !tile::DIRECTIONS.contains(&orientation.as_str()) {
+                    return Err(format!("Invalid orientation '{}'",
+                                       orientation));
+                }
+                let amounts = self.manifest.amounts(&self.log, &self.map);
+                match amounts.get(tile) {
+                    None => Err(format!("Tile {} is not in the manifest",
+                                        tile)),
+                    Some(&0) => Err(format!("There are no more {} tiles \
+                                             available", tile)),
+                    Some(_) => Ok(()),
+                }
+            }
+            Action::Token { ref location, ref company, city } => {
+                let location = self.hex(location)?;
+                let name = Location::from_coord(location, &self.map);
+                if !self.companies.contains_key(company) {
+                    return Err(format!("Unknown company {}", company));
+                }
+                let city = city.unwrap_or(0) as usize;
+                let circles = top_tiles(&self.placed_tiles(),
+                                        &self.map.tiles())
+                    .get(&location)
+                    .and_then(|tile| tile.cities().get(city).cloned())
+                    .map(|city| city.circles)
+                    .ok_or(format!("There is no station {} in {}", city,
+                                   name))?;
+                let tokens = self.tokens();
+                let tokens: Vec<_> = tokens.get(&location)
+                    .map(|tokens| tokens.iter()
+                         .filter(|t| t.station == city)
+                         .collect())
+                    .unwrap_or_default();
+                if tokens.iter().any(|t| t.name == *company && t.is_home) {
+                    Ok(())
+                } else if tokens.len() as u32 >= circles {
+                    Err(format!("There is no room for another token in {}",
+                                name))
+                } else {
+                    Ok(())
+                }
+            }
+            Action::RemoveCompany { ref company } => {
+                if self.companies.contains_key(company) {
+                    Ok(())
+                } else {
+                    Err(format!("Unknown company {}", company))
+                }
+            }
+        }
+    }
+
+    /// Coordinate of a location that must be a hex on the map
+    fn hex(&self, location: &Location) -> Result<(u32, u32), String> {
+        let coord = location.to_coord(&self.map)?;
+        if self.map.locations().contains(&coord) {
+            Ok(coord)
+        } else {
+            Err(format!("There is no hex at {}",
+                        Location::from_coord(coord, &self.map)))
+        }
+    }
+
     pub fn placed_tiles(&self) -> HashMap<(u32, u32), PlacedTile> {
         let mut placed = HashMap::new();
         if let Some(ref log) = self.log {
             for action in log.log.iter() {
                 if let &Action::TileLay {ref location, ref tile,
                                          ref orientation} = action {
-                    let location = location.as_coord(&self.map.orientation);
-                    let t = PlacedTile::new_from(self.manifest.tiles.iter()
-                            .find(|t| t.name() == tile).unwrap())
+                    let location = location.as_coord(&self.map);
+                    let t = match self.manifest.tiles.iter()
+                            .find(|t| t.name() == tile) {
+                        Some(t) => t,
+                        None => {
+                            eprintln!("Could not lay tile {} in {}: tile is \
+                                      not in the manifest", tile,
+                                      Location::from_coord(location,
+                                                           &self.map));
+                            continue;
+                        }
+                    };
+                    let t = PlacedTile::new_from(t)
                         .set_orientation(
                             tile::direction_to_angle(orientation));
                     placed.insert(location, t);
@@ -217,11 +674,22 @@ impl Game {
     }
 
     pub fn tokens(&self) -> HashMap<(u32, u32), Vec<Token>> {
+        self.tokens_on(&self.placed_tiles())
+    }
+
+    /// The tokens on the map when the tiles that are laid are known
+    fn tokens_on(&self, placed: &HashMap<(u32, u32), PlacedTile>)
+            -> HashMap<(u32, u32), Vec<Token>> {
+        let map_tiles = self.map.tiles();
+        let top = top_tiles(placed, &map_tiles);
         let mut tokens = HashMap::new();
-        for (name, company) in self.companies.iter() {
+        // Home tokens in the same place are added in the same order each time
+        let mut companies: Vec<_> = self.companies.iter().collect();
+        companies.sort_by_key(|&(name, _)| name);
+        for (name, company) in companies {
             if let Some(ref home) = company.home {
                 let token = Token::from(&home, name, &company.color,
-                                        &self.map.orientation).set_home();
+                                        &self.map).set_home();
                 tokens.entry(token.location).or_insert(vec![]).push(token);
             }
         }
@@ -229,12 +697,25 @@ impl Game {
         if let Some(ref log) = self.log {
             'next_action: for act in log.log.iter() {
                 if let &Action::Token {ref location, ref company, city} = act {
-                    let location = location.as_coord(&self.map.orientation);
-                    let entry = tokens.entry(location).or_insert(vec![]);
+                    let location = location.as_coord(&self.map);
                     let city = match city {
                         Some(id) => id,
                         None => 0,
                     } as usize;
+                    let name = Location::from_coord(location, &self.map);
+                    let circles = top.get(&location)
+                        .and_then(|tile| tile.cities().get(city).cloned())
+                        .map(|city| city.circles);
+                    let circles = match circles {
+                        Some(circles) => circles,
+                        None => {
+                            eprintln!("Could not place token for {} in {}: \
+                                      there is no station {}", company, name,
+                                      city);
+                            continue;
+                        }
+                    };
+                    let entry = tokens.entry(location).or_insert(vec![]);
                     let mut placed = 0;
                     for token in entry.iter_mut() {
                         if token.name == company.as_str() && token.is_home {
@@ -245,18 +726,17 @@ impl Game {
                             placed += 1;
                         }
                     }
-                    if placed >= top_tiles(&self.placed_tiles(),
-                                           &self.map.tiles())
-                                     .get(&location).unwrap()
-                                     .cities().get(city).unwrap().circles
-                    {
-                        eprintln!("Could not place token for {} in {:?}: \
-                                  too many tokens in station", company,
-                                  location);
-                    } else {
-                        entry.push(
-                            Token::place(self.companies.get(company).unwrap(),
-                                         company, location, city, placed));
+                    match self.companies.get(company) {
+                        None => eprintln!("Could not place token for {} in \
+                                          {}: unknown company", company,
+                                          name),
+                        Some(_) if placed >= circles =>
+                            eprintln!("Could not place token for {} in {}: \
+                                      too many tokens in station", company,
+                                      name),
+                        Some(info) => entry.push(
+                            Token::place(info, company, location, city,
+                                         placed)),
                     }
                 }
                 else if let &Action::RemoveCompany {ref company} = act {
@@ -266,7 +746,10 @@ impl Game {
                         entry.sort_by(|a, b| a.station.cmp(&b.station));
 
                         // Reorder remaining tokens
-                        let mut station = entry[0].station;
+                        let mut station = match entry.first() {
+                            Some(token) => token.station,
+                            None => continue,
+                        };
                         let mut placed = 0;
                         for token in entry.iter_mut() {
                             if token.station != station {
@@ -283,6 +766,143 @@ impl Game {
 
         tokens
     }
+
+    /// Replay the log of the game
+    pub fn replay(&self) -> Replay<'_> {
+        let placed = self.placed_tiles();
+        let tokens = self.tokens_on(&placed);
+        Replay {
+            placed,
+            tokens,
+            amounts: self.manifest.amounts(&self.log, &self.map),
+        }
+    }
+
+    /// The state of the game after replaying its log
+    pub fn state(&self) -> State {
+        let replay = self.replay();
+        let tiles = self.map.tiles();
+        let top = top_tiles(&replay.placed, &tiles);
+        let tokens = &replay.tokens;
+        let mut locations: Vec<_> = top.keys().cloned().collect();
+        locations.sort();
+        let hexes = locations.iter().map(|location| {
+            let tile = top[location];
+            let mut cities: Vec<CityState> = tile.cities().iter()
+                .map(|city| CityState {
+                    slots: vec![None; city.circles as usize],
+                })
+                .collect();
+            for token in tokens.get(location).unwrap_or(&vec![]) {
+                let slot = cities.get_mut(token.station)
+                    .and_then(|city| city.slots
+                              .get_mut(token.circle as usize));
+                if let Some(slot) = slot {
+                    *slot = Some(TokenState {
+                        company: token.name.clone(),
+                        home: token.is_home,
+                    });
+                }
+            }
+            HexState {
+                location: Location::from_coord(*location, &self.map)
+                    .to_string(),
+                tile: Some(tile.name().to_string())
+                    .filter(|name| !name.is_empty()),
+                orientation: tile::angle_to_direction(tile.orientation()),
+                color: tile::colors::color_to_name(&tile.color())
+                    .map(|name| name.to_string())
+                    .unwrap_or(tile.color().value().to_string()),
+                cities,
+                revenue_location: self.map.area(*location).map(|_| {
+                    let revenue = self.map.revenue_location(*location);
+                    Location::from_coord(revenue, &self.map).to_string()
+                }),
+            }
+        }).collect();
+
+        let mut companies = BTreeMap::new();
+        for (name, company) in self.companies.iter() {
+            let mut placed: Vec<_> = tokens.values()
+                .flat_map(|tokens| tokens.iter())
+                .filter(|token| token.name == *name && !token.is_home)
+                .map(|token| token.location)
+                .collect();
+            placed.sort();
+            companies.insert(name.clone(), CompanyState {
+                name: company.name.clone(),
+                color: company.color.clone(),
+                tokens: placed.iter()
+                    .map(|l| Location::from_coord(*l, &self.map).to_string())
+                    .collect(),
+            });
+        }
+
+        State {
+            game: self.log.as_ref()
+                .map(|log| log.game_name.clone())
+                .unwrap_or_default(),
+            hexes,
+            remaining: replay.amounts.into_iter().collect(),
+            companies,
+        }
+    }
+}
+
+/// What the log of a game has done, worked out once so that it can be used
+/// while drawing
+pub struct Replay<'a> {
+    /// Tiles that have been laid by location
+    pub placed: HashMap<(u32, u32), PlacedTile<'a>>,
+    /// Tokens on the map by location
+    pub tokens: HashMap<(u32, u32), Vec<Token>>,
+    /// Number of each tile that is still available
+    pub amounts: HashMap<String, u32>,
+}
+
+/// Resolved state of a game, meant to be read by other programs
+#[derive(Serialize)]
+pub struct State {
+    pub game: String,
+    pub hexes: Vec<HexState>,
+    /// Number of each tile that is still available
+    pub remaining: BTreeMap<String, u32>,
+    pub companies: BTreeMap<String, CompanyState>,
+}
+
+/// The tile on a hex of the map and the tokens on it
+#[derive(Serialize)]
+pub struct HexState {
+    pub location: String,
+    /// Name of the tile, pre-printed hexes don't have one
+    pub tile: Option<String>,
+    pub orientation: &'static str,
+    pub color: String,
+    pub cities: Vec<CityState>,
+    /// Hex whose revenue is counted for this one, only set for area hexes
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub revenue_location: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CityState {
+    /// Token in each of the token circles of the city
+    pub slots: Vec<Option<TokenState>>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TokenState {
+    pub company: String,
+    /// Whether the circle is only reserved for the company's home token
+    pub home: bool,
+}
+
+#[derive(Serialize)]
+pub struct CompanyState {
+    pub name: String,
+    pub color: String,
+    /// Locations of the tokens that have been placed
+    pub tokens: Vec<String>,
 }
 
 pub fn top_tiles<'a>(placed: &'a HashMap<(u32, u32), PlacedTile>,
@@ -295,15 +915,20 @@ pub fn top_tiles<'a>(placed: &'a HashMap<(u32, u32), PlacedTile>,
     map
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Manifest {
+    #[serde(default="schema::unmarked")]
+    pub version: u32,
     pub tiles: Vec<tile::Tile>,
+    #[serde(serialize_with="tile::serialize_sorted")]
     amounts: HashMap<String, u32>,
 }
 
+
 impl Default for Manifest {
     fn default() -> Manifest {
         Manifest {
+            version: schema::VERSION,
             tiles: vec![],
             amounts: HashMap::new(),
         }
@@ -311,6 +936,12 @@ impl Default for Manifest {
 }
 
 impl Manifest {
+    /// Add a tile together with the number of copies that are available
+    pub fn add_tile(&mut self, tile: tile::Tile, amount: u32) {
+        self.amounts.insert(tile.name().to_string(), amount);
+        self.tiles.push(tile);
+    }
+
     pub fn get_tile(&self, name: &String) -> Result<&tile::Tile, String> {
         for tile in &self.tiles {
             if tile.name() == name {
@@ -320,7 +951,8 @@ impl Manifest {
         Err(format!("Tile with name '{}' not found in manifest", name))
     }
 
-    pub fn amounts(&self, log: &Option<Log>) -> HashMap<String, u32> {
+    pub fn amounts(&self, log: &Option<Log>,
+                   map: &Map) -> HashMap<String, u32> {
         match log {
             // Don't bother with the amount if there is no log
             &None => self.amounts.clone(),
@@ -330,11 +962,7 @@ impl Manifest {
                 for action in log.log.iter() {
                     if let &Action::TileLay{ref location, ref tile,
                                             ..} = action {
-                        // For calculating manifest amounts we don't care about
-                        // the exact rendered position, just the coordinate so
-                        // we can use any orientation
-                        let location = location
-                            .as_coord(&Orientation::Horizontal);
+                        let location = location.as_coord(map);
                         let old_tile = placed.insert(location, tile);
                         if let Some(old_tile) = old_tile {
                             *used.entry(old_tile).or_insert(0) -= 1;
@@ -343,7 +971,10 @@ impl Manifest {
                     }
                 }
                 self.amounts.iter()
-                    .map(|(k, v)| (k.clone(), v - *used.get(k).unwrap_or(&0)))
+                    .map(|(k, v)| {
+                        let used = *used.get(k).unwrap_or(&0);
+                        (k.clone(), v.saturating_sub(used))
+                    })
                     .collect()
             }
 
@@ -351,26 +982,52 @@ impl Manifest {
     }
 }
 
-#[derive(Clone,Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MapTile {
     pub location: Location,
     #[serde(default="MapTile::default_tile")]
     pub tile: String,
 
     // Optional parameters
-    color: Option<String>,
-    orientation: Option<String>,
-    #[serde(default)]
-    text: HashMap<String, String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub orientation: Option<String>,
+    #[serde(default, skip_serializing_if="HashMap::is_empty",
+            serialize_with="tile::serialize_sorted")]
+    pub text: HashMap<String, String>,
+    #[serde(skip_serializing_if="Option::is_none")]
     arrows: Option<Vec<tile::Coordinate>>,
-    revenue: Option<tile::RevenueTrack>,
-    terrain: Option<tile::Terrain>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub revenue: Option<tile::RevenueTrack>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub terrain: Vec<tile::Terrain>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    icons: Vec<tile::Icon>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    edges: Vec<EdgeDecoration>,
 
     #[serde(skip)]
     definition: Option<tile::TileDefinition>,
 }
 
 impl MapTile {
+    pub fn new(location: Location, tile: String) -> MapTile {
+        MapTile {
+            location,
+            tile,
+            color: None,
+            orientation: None,
+            text: HashMap::new(),
+            arrows: None,
+            revenue: None,
+            terrain: vec![],
+            icons: vec![],
+            edges: vec![],
+            definition: None,
+        }
+    }
+
     pub fn set_definition(&mut self, definition: &tile::TileDefinition) {
         self.definition = Some(definition.clone())
     }
@@ -418,6 +1075,14 @@ impl TileSpec for MapTile {
            .is_lawson()
    }
 
+   fn icons(&self) -> Vec<tile::Icon> {
+       let mut icons = self.definition.as_ref()
+           .expect("You must call set_definition() before using icons()")
+           .icons();
+       icons.extend(self.icons.iter().cloned());
+       icons
+   }
+
     fn get_text(&self, id: &str) -> &str {
         match self.text.get(id) {
             Some(s) => s,
@@ -457,9 +1122,20 @@ impl TileSpec for MapTile {
        self.revenue.clone()
    }
 
-   fn terrain(&self) -> Option<tile::Terrain> {
+   fn terrain(&self) -> Vec<tile::Terrain> {
        self.terrain.clone()
    }
+
+    /// Everything but the location and the decorations on the edges, which
+    /// are drawn separately
+    fn drawing_key(&self) -> Option<String> {
+        let mut value = serde_json::to_value(self).unwrap();
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("location");
+            fields.remove("edges");
+        }
+        Some(format!("map:{}", value))
+    }
 }
 
 pub struct PlacedTile<'a> {
@@ -491,6 +1167,7 @@ impl<'a> TileSpec for PlacedTile<'a> {
     fn cities(&self) -> Vec<tile::City> { self.base_tile.cities() }
     fn stops(&self) -> Vec<tile::Stop> { self.base_tile.stops() }
     fn is_lawson(&self) -> bool { self.base_tile.is_lawson() }
+    fn icons(&self) -> Vec<tile::Icon> { self.base_tile.icons() }
     fn arrows(&self) -> Vec<tile::Coordinate> { self.base_tile.arrows() }
     fn revenue_track(&self) -> Option<tile::RevenueTrack> {
         self.base_tile.revenue_track()
@@ -505,16 +1182,142 @@ impl<'a> TileSpec for PlacedTile<'a> {
     fn orientation(&self) -> f64 {
         self.orientation
     }
+    fn drawing_key(&self) -> Option<String> {
+        self.base_tile.drawing_key()
+            .map(|key| format!("{}@{}", key, self.orientation))
+    }
+}
+
+/// An SVG image that can be referred to as an icon
+pub struct IconSymbol {
+    pub view_box: String,
+    /// The SVG elements that make up the image
+    pub content: String,
 }
 
-#[derive(Clone,Deserialize)]
+impl IconSymbol {
+    /// Extract the contents of an SVG file so it can be embedded
+    pub fn parse(svg: &str) -> Result<IconSymbol, String> {
+        let start = svg.find("<svg")
+            .ok_or("no <svg> element found")?;
+        let tag_end = start + svg[start..].find('>')
+            .ok_or("unterminated <svg> element")?;
+        let end = svg.rfind("</svg>")
+            .ok_or("no closing </svg> tag found")?;
+        if end < tag_end {
+            return Err("no closing </svg> tag found".to_string());
+        }
+        let tag = svg[start..tag_end].replace(|c: char| c.is_whitespace(),
+                                              " ");
+        let view_box = match IconSymbol::attribute(&tag, "viewBox") {
+            Some(view_box) => view_box,
+            None => {
+                // Fall back to the size of the image
+                let width = IconSymbol::attribute(&tag, "width");
+                let height = IconSymbol::attribute(&tag, "height");
+                match (width, height) {
+                    (Some(w), Some(h)) => format!("0 0 {} {}", w, h),
+                    _ => return Err("missing viewBox or width and height \
+                                    attributes".to_string()),
+                }
+            }
+        };
+        Ok(IconSymbol {
+            view_box,
+            content: svg[tag_end+1..end].trim().to_string(),
+        })
+    }
+
+    /// Reads all SVG files in a directory, the file name is the icon name
+    pub fn read_all(dir: PathBuf)
+            -> Result<HashMap<String, IconSymbol>, String> {
+        let files: Vec<PathBuf> = fs::read_dir(&dir)
+            .map_err(|err| {
+                format!("Couldn't open icons directory: {:?}", err.kind())
+            })?
+            .map(|path| path.unwrap().path())
+            .collect();
+        let mut icons = HashMap::new();
+        for file in files.iter() {
+            if file.extension().is_none_or(|ext| ext != "svg") {
+                continue;
+            }
+            let mut content = String::new();
+            File::open(file)
+                .and_then(|mut f| f.read_to_string(&mut content))
+                .map_err(|err| {
+                    format!("Couldn't read {}: {:?}", file.to_string_lossy(),
+                            err.kind())
+                })?;
+            let icon = IconSymbol::parse(&content).map_err(|err| {
+                format!("Failed to parse icon {}: {}",
+                        file.to_string_lossy(), err)
+            })?;
+            icons.insert(file.file_stem().unwrap().to_string_lossy()
+                         .into_owned(), icon);
+        }
+        Ok(icons)
+    }
+
+    /// Find the value of an attribute in an element's opening tag
+    fn attribute(tag: &str, name: &str) -> Option<String> {
+        for quote in ["\"", "'"].iter() {
+            let pattern = format!(" {}={}", name, quote);
+            if let Some(pos) = tag.find(&pattern) {
+                let value = &tag[pos + pattern.len()..];
+                if let Some(end) = value.find(quote) {
+                    return Some(value[..end].to_string());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Group of hexes that act as a single location, such as a large off-board
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Area {
+    pub name: String,
+    pub locations: Vec<Location>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub revenue: Option<tile::RevenueTrack>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Barrier {
     pub location: Location,
     pub side: String,
 }
 
+/// Decoration that is drawn along one of the sides of a hex
+#[derive(Clone, Deserialize, Serialize)]
+pub struct EdgeDecoration {
+    pub side: String,
+    #[serde(rename="type")]
+    pub decoration_type: EdgeDecorationType,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub cost: Option<String>,
+}
+
+/// Types of decoration that can be drawn on the side of a hex
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum EdgeDecorationType {
+    /// River or lake that has to be crossed
+    Water,
+    /// Shading along a coastline
+    Coast,
+    Port,
+    Ferry,
+    Tunnel,
+    /// National border, drawn as a dashed line
+    Border,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct Log {
+    #[serde(default="schema::unmarked")]
+    pub version: u32,
     pub game_name: String,
     pub log: Box<[Action]>,
 }
@@ -522,6 +1325,7 @@ pub struct Log {
 impl Log {
     pub fn new() -> Log {
         Log {
+            version: schema::VERSION,
             game_name: "1830".to_string(),
             log: Box::new([]),
         }
@@ -529,6 +1333,7 @@ impl Log {
 
     pub fn new_game(game: String) -> Log {
         Log {
+            version: schema::VERSION,
             game_name: game,
             log: Box::new([]),
         }
@@ -536,22 +1341,42 @@ impl Log {
 
     pub fn load(state_options: &super::StateOptions,
                 _options: &super::Options) -> Log {
-        let log: Log;
-        let log_filename = format!("{}.yaml", state_options.name);
         println!("Reading log from file...");
-        match File::open(log_filename) {
-            Err(e) => {
-                eprintln!("Failed to find game file: {}", e);
+        Log::read(&format!("{}.yaml", state_options.name))
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
                 process::exit(1);
-            }
-            Ok(file) => {
-                log = serde_yaml::from_reader(file).unwrap_or_else(|err| {
-                    eprintln!("Failed to load game: {}", err);
-                    process::exit(1);
-                });
-            }
-        }
-        log
+            })
+    }
+
+    /// Read a log from a file
+    pub fn read(filename: &str) -> Result<Log, String> {
+        let text = fs::read_to_string(filename).map_err(|e| {
+            format!("Failed to find game file: {}", e)
+        })?;
+        schema::check_yaml(schema::Kind::Log, &text, filename)?;
+        let mut log: Log = serde_yaml::from_str(&text).map_err(|err| {
+            format!("Failed to load game: {}", err)
+        })?;
+        log.version = schema::VERSION;
+        Ok(log)
+    }
+
+    /// Write the log to a file, replacing what was there
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        let file = File::create(filename).map_err(|e| {
+            format!("Couldn't write game file {}: {}", filename, e)
+        })?;
+        serde_yaml::to_writer(file, self).map_err(|err| {
+            format!("Couldn't write game file {}: {}", filename, err)
+        })
+    }
+
+    /// Add an action to the end of the log
+    pub fn push(&mut self, action: Action) {
+        let mut log = self.log.to_vec();
+        log.push(action);
+        self.log = log.into_boxed_slice();
     }
 }
 
@@ -563,17 +1388,42 @@ pub enum Action {
     RemoveCompany { company: String },
 }
 
-#[derive(Clone,Deserialize)]
+impl Action {
+    /// Short description of the action for players to read
+    pub fn describe(&self, map: &Map) -> String {
+        match *self {
+            Action::TileLay { ref location, ref tile, ref orientation } =>
+                format!("Lay tile {} in {} facing {}", tile,
+                        Location::from_coord(location.as_coord(map), map),
+                        orientation),
+            Action::Token { ref location, ref company, city } => {
+                let location = Location::from_coord(location.as_coord(map),
+                                                    map);
+                match city {
+                    Some(city) => format!("Place {} token in {} (city {})",
+                                          company, location, city),
+                    None => format!("Place {} token in {}", company,
+                                    location),
+                }
+            }
+            Action::RemoveCompany { ref company } =>
+                format!("Remove {}", company),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Home {
     PositionOnly(Location),
     PositionStation(Location, usize),
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Company {
     pub name: String,
     pub color: String,
+    #[serde(skip_serializing_if="Option::is_none")]
     pub home: Option<Home>,
 }
 
@@ -589,7 +1439,7 @@ pub struct Token {
 impl Token {
     pub fn from(home: &Home, name: &str,
                 color: &str,
-                orientation: &Orientation) -> Self {
+                map: &Map) -> Self {
         let mut token = Token {
             name: name.to_string(),
             color: color.to_string(),
@@ -600,9 +1450,9 @@ impl Token {
         };
         match home {
             &Home::PositionOnly(ref loc) =>
-                token.location = loc.as_coord(orientation),
+                token.location = loc.as_coord(map),
             &Home::PositionStation(ref loc, s) => {
-                token.location = loc.as_coord(orientation);
+                token.location = loc.as_coord(map);
                 token.station = s;
             }
         }
@@ -629,3 +1479,284 @@ impl Token {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(orientation: Orientation, letters: Option<Axis>, parity: Parity,
+           first_letter: &str, first_number: u32) -> Map {
+        Map {
+            orientation,
+            coordinates: Coordinates {
+                letters,
+                parity,
+                first_letter: first_letter.to_string(),
+                first_number,
+            },
+            ..Map::default()
+        }
+    }
+
+    /// The coordinate scheme of 1830
+    fn map_1830() -> Map {
+        map(Orientation::Vertical, None, Parity::Odd, "A", 1)
+    }
+
+    fn name(location: (u32, u32), map: &Map) -> String {
+        Location::from_coord(location, map).to_string()
+    }
+
+    #[test]
+    fn names_on_1830_map() {
+        let map = map_1830();
+        assert_eq!(name((0, 0), &map), "A1");
+        assert_eq!(name((9, 4), &map), "E19");
+        assert_eq!(name((5, 3), &map), "D12");
+        assert_eq!(Location::parse("E19", &map), Ok((9, 4)));
+        assert_eq!(Location::parse("D12", &map), Ok((5, 3)));
+        assert_eq!(Location::parse("AA1", &map), Ok((0, 26)));
+    }
+
+    #[test]
+    fn round_trip_every_scheme() {
+        let orientations = [Orientation::Horizontal, Orientation::Vertical];
+        let letters = [None, Some(Axis::Columns), Some(Axis::Rows)];
+        let parities = [Parity::Odd, Parity::Even];
+        let firsts = [("A", 1), ("B", 0), ("Z", 10)];
+        for orientation in orientations.iter() {
+            for &axis in letters.iter() {
+                for &parity in parities.iter() {
+                    for &(letter, number) in firsts.iter() {
+                        let map = map(orientation.clone(), axis, parity,
+                                      letter, number);
+                        for x in 0..30 {
+                            for y in 0..30 {
+                                let name = name((x, y), &map);
+                                assert_eq!(Location::parse(&name, &map),
+                                           Ok((x, y)), "{}", name);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parity_shifts_the_numbers() {
+        let odd = map(Orientation::Horizontal, None, Parity::Odd, "A", 1);
+        let even = map(Orientation::Horizontal, None, Parity::Even, "A", 1);
+        assert_eq!(name((0, 0), &odd), "A1");
+        assert_eq!(name((1, 0), &odd), "B2");
+        assert_eq!(name((0, 0), &even), "A2");
+        assert_eq!(name((1, 0), &even), "B1");
+        let rows = map(Orientation::Horizontal, Some(Axis::Rows), Parity::Odd,
+                       "A", 1);
+        assert_eq!(name((1, 0), &rows), "B2");
+        assert_eq!(name((0, 1), &rows), "C1");
+    }
+
+    #[test]
+    fn coordinate_labels() {
+        let map = map(Orientation::Horizontal, Some(Axis::Rows), Parity::Odd,
+                      "C", 0);
+        let coords = &map.coordinates;
+        assert!(coords.letters(&map.orientation) == Axis::Rows);
+        assert_eq!(coords.label(0, Axis::Rows, &map.orientation), "C");
+        assert_eq!(coords.label(24, Axis::Rows, &map.orientation), "AA");
+        assert_eq!(coords.label(0, Axis::Columns, &map.orientation), "0");
+        let map = map_1830();
+        assert!(map.coordinates.letters(&map.orientation) == Axis::Rows);
+        assert_eq!(map.coordinates.label(18, Axis::Columns, &map.orientation),
+                   "19");
+    }
+
+    #[test]
+    fn malformed_locations() {
+        let map = map_1830();
+        for name in ["", "G", "19", "19G", "G19A", "G-1", "G 19", "É19",
+                     "G١٩"].iter() {
+            assert_eq!(Location::parse(name, &map),
+                       Err(format!("Invalid location '{}': expected letters \
+                                   followed by a number, such as 'G19'",
+                                   name)));
+        }
+        assert_eq!(Location::parse("g19", &map),
+                   Err(String::from("Invalid location 'g19': letters must \
+                                    be upper case")));
+        assert_eq!(Location::parse("D11", &map),
+                   Err(String::from("Invalid location 'D11': no hex has \
+                                    this name, did you mean 'D12'?")));
+        assert_eq!(Location::parse("G99999999999", &map),
+                   Err(String::from("Invalid location 'G99999999999': \
+                                    number too large to fit in target \
+                                    type")));
+        assert_eq!(Location::parse("ABCDEFGHIJ1", &map),
+                   Err(String::from("Invalid location 'ABCDEFGHIJ1': \
+                                    'ABCDEFGHIJ' has too many letters")));
+    }
+
+    #[test]
+    fn locations_before_the_first_hex() {
+        let map = map(Orientation::Vertical, None, Parity::Odd, "B", 2);
+        assert_eq!(Location::parse("B2", &map), Ok((0, 0)));
+        assert_eq!(Location::parse("A2", &map),
+                   Err(String::from("Invalid location 'A2': the first hex \
+                                    is at letter B and number 2")));
+        assert_eq!(Location::parse("B1", &map),
+                   Err(String::from("Invalid location 'B1': the first hex \
+                                    is at letter B and number 2")));
+    }
+
+    fn read_map(yaml: &str) -> Map {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn check_map_locations() {
+        let map = read_map(r#"{
+            "orientation": "Vertical", "scale": 3.81,
+            "barriers": [{"location": "A3", "side": "NE"}],
+            "areas": [{"name": "West", "locations": ["A1", "B2"]}],
+            "tiles": [
+                {"location": "A1", "orientation": "SW",
+                    "edges": [{"side": "S", "type": "water"}]},
+                {"location": "B2"}, {"location": "A3"}
+            ]
+        }"#);
+        assert!(map.check_locations().is_ok());
+
+        let invalid = [
+            (r#""barriers": [{"location": "A2", "side": "N"}]"#,
+             "Invalid location 'A2': no hex has this name, did you mean \
+              'A1'?"),
+            (r#""barriers": [{"location": "A1", "side": "W"}]"#,
+             "Invalid side of the barrier in A1: Invalid direction 'W', \
+              expected one of N, NE, SE, S, SW, NW"),
+            (r#""areas": [{"name": "West", "locations": ["a1"]}]"#,
+             "Invalid location in area 'West': Invalid location 'a1': \
+              letters must be upper case"),
+            (r#""tiles": [{"location": "A1", "orientation": "up"}]"#,
+             "Invalid orientation of A1: Invalid direction 'up', expected \
+              one of N, NE, SE, S, SW, NW"),
+            (r#""tiles": [{"location": "A1",
+                           "edges": [{"side": "C", "type": "coast"}]}]"#,
+             "Invalid side of an edge of A1: Invalid direction 'C', \
+              expected one of N, NE, SE, S, SW, NW"),
+        ];
+        for &(field, message) in invalid.iter() {
+            let tiles = if field.contains("\"tiles\"") {
+                ""
+            } else {
+                r#""tiles": [{"location": "A1"}],"#
+            };
+            let map = read_map(&format!(r#"{{
+                "orientation": "Vertical", "scale": 3.81, {} {}
+            }}"#, tiles, field));
+            assert_eq!(map.check_locations(), Err(message.to_string()));
+        }
+    }
+
+    #[test]
+    fn invalid_position_codes_are_rejected() {
+        let result: Result<Map, _> = serde_yaml::from_str(r#"{
+            "orientation": "Vertical", "scale": 3.81,
+            "tiles": [{"location": "A1", "arrows": ["W"]}]
+        }"#);
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("invalid position code 'W'"), "{}", err);
+    }
+
+    fn game_1830() -> Game {
+        let definitions = tile::read_definitions(&::Options::new()).unwrap();
+        Game::read(["games", "1830"].iter().collect(), &definitions).unwrap()
+    }
+
+    #[test]
+    fn remove_only_company_on_a_hex() {
+        let remove = Action::RemoveCompany { company: "B&M".to_string() };
+        let game = game_1830();
+        assert!(game.validate(&remove).is_ok());
+        let mut log = Log::new();
+        log.push(remove);
+        let game = game.set_log(log);
+        let home = Location::parse("E23", &game.map).unwrap();
+        assert!(game.tokens().get(&home).is_none_or(|t| t.is_empty()));
+        assert!(!game.state().companies["B&M"].tokens.iter()
+                .any(|t| t == "E23"));
+        ::draw::draw_map(&game, &::Options::new());
+    }
+
+    #[test]
+    fn gulf_of_mexico_area() {
+        let game = game_1830();
+        let i1 = Location::parse("I1", &game.map).unwrap();
+        let j2 = Location::parse("J2", &game.map).unwrap();
+        assert_eq!(game.map.area(j2).map(|a| a.name.as_str()),
+                   Some("Gulf of Mexico"));
+        assert_eq!(game.map.revenue_location(j2), i1);
+        assert_eq!(game.map.revenue_location(i1), i1);
+
+        let state = game.state();
+        let revenue = |location: &str| state.hexes.iter()
+            .find(|hex| hex.location == location)
+            .and_then(|hex| hex.revenue_location.clone());
+        assert_eq!(revenue("J2"), Some(String::from("I1")));
+        assert_eq!(revenue("I1"), Some(String::from("I1")));
+        assert_eq!(revenue("E19"), None);
+
+        // Both hexes are outlined together, as one closed shape
+        let svg = ::draw::draw_map(&game, &::Options::new()).to_string();
+        assert_eq!(svg.matches("data-area=\"Gulf of Mexico\"").count(), 1);
+        let outline = svg.lines()
+            .find(|line| line.contains("data-area=\"Gulf of Mexico\""))
+            .unwrap();
+        assert_eq!(outline.matches('L').count(), 9);
+        assert_eq!(outline.matches('z').count(), 1);
+    }
+
+    #[test]
+    fn invalid_actions() {
+        let game = game_1830();
+        let lay = |location: &str, orientation: &str| Action::TileLay {
+            location: Location::Named(location.to_string()),
+            tile: "57".to_string(),
+            orientation: orientation.to_string(),
+        };
+        assert!(game.validate(&lay("E19", "N")).is_ok());
+        assert_eq!(game.validate(&lay("E18", "N")),
+                   Err(String::from("Invalid location 'E18': no hex has \
+                                    this name, did you mean 'E17'?")));
+        assert_eq!(game.validate(&lay("E19", "W")),
+                   Err(String::from("Invalid orientation 'W'")));
+        assert_eq!(game.validate(&Action::RemoveCompany {
+                       company: "PRR ".to_string() }),
+                   Err(String::from("Unknown company PRR ")));
+
+        let mut log = Log::new();
+        log.push(lay("E19", "N"));
+        log.push(lay("e19", "N"));
+        let game = game.set_log(log);
+        assert_eq!(game.check_log(),
+                   Err(String::from("Invalid action 2 in the log: Invalid \
+                                    location 'e19': letters must be upper \
+                                    case")));
+    }
+
+    #[test]
+    fn letters() {
+        assert_eq!(letter_to_number("A"), Ok(0));
+        assert_eq!(letter_to_number("Z"), Ok(25));
+        assert_eq!(letter_to_number("AA"), Ok(26));
+        assert_eq!(letter_to_number("BA"), Ok(52));
+        assert_eq!(letter_to_number(""),
+                   Err(String::from("no letters given")));
+        assert_eq!(letter_to_number("A1"),
+                   Err(String::from("'1' is not a letter")));
+        assert!(letter_to_number("ZZZZZZZ").is_err());
+        for n in [0, 1, 25, 26, 27, 51, 52, 701, 702, 100000].iter() {
+            assert_eq!(letter_to_number(&number_to_letter(*n)), Ok(*n));
+        }
+    }
+}
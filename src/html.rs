@@ -0,0 +1,149 @@
+//! Standalone HTML viewer for the state of a game
+//!
+//! The viewer contains the map, information about each hex and a side panel
+//! with the remaining tiles and the history of the game. Everything is put in
+//! a single file so that it can be shared with players who don't have
+//! map18xx.
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+
+use draw;
+use draw::escape;
+use game;
+use game::Location;
+use tile::TileSpec;
+
+/// Information about a hex that is shown when hovering over it
+#[derive(Serialize)]
+struct HexInfo {
+    tile: Option<String>,
+    /// Sum of the printed terrain costs, only for display
+    terrain_cost: u32,
+    tokens: Vec<String>,
+}
+
+/// Collect the information of each hex, indexed by the name of the hex
+fn hex_info(game: &game::Game) -> BTreeMap<String, HexInfo> {
+    let replay = game.replay();
+    let tiles = game.map.tiles();
+    let top = game::top_tiles(&replay.placed, &tiles);
+    let tokens = &replay.tokens;
+    game.map.locations().iter()
+        .map(|location| {
+            let tile = top.get(location)
+                .map(|t| t.name().to_string())
+                .filter(|name| !name.is_empty());
+            let terrain_cost = tiles.get(location)
+                .map(|t| t.terrain().iter().map(|t| t.cost_value()).sum())
+                .unwrap_or(0);
+            let tokens = tokens.get(location)
+                .map(|tokens| tokens.iter().map(|t| t.name.clone()).collect())
+                .unwrap_or_default();
+            (Location::from_coord(*location, &game.map).to_string(),
+             HexInfo { tile, terrain_cost, tokens })
+        })
+        .collect()
+}
+
+/// Side panel with the tiles that are left and the actions taken so far
+fn side_panel(game: &game::Game) -> String {
+    let amounts = game.manifest.amounts(&game.log, &game.map);
+    let mut panel = String::from("<h2>Tiles</h2>\n<table>\n");
+    for tile in game.manifest.tiles.iter() {
+        panel += &format!("<tr><td>{}</td><td>{}×</td></tr>\n",
+                          escape(tile.name()),
+                          amounts.get(tile.name()).unwrap_or(&0));
+    }
+    panel += "</table>\n<h2>History</h2>\n<ol>\n";
+    if let Some(ref log) = game.log {
+        for action in log.log.iter() {
+            panel += &format!("<li>{}</li>\n",
+                              escape(&action.describe(&game.map)));
+        }
+    }
+    panel += "</ol>\n";
+    panel
+}
+
+/// Render a game as a single HTML page
+pub fn draw_viewer(game: &game::Game, options: &::Options,
+                   title: &str) -> String {
+    let map = draw::draw_map(game, options).to_string();
+    // Prevent the data from closing the script element early
+    let info = serde_json::to_string(&hex_info(game)).unwrap()
+        .replace("</", "<\\/");
+    fill(include_str!("viewer.html"), &[
+        ("title", &escape(title)),
+        ("panel", &side_panel(game)),
+        ("info", &info),
+        ("map", &map),
+    ])
+}
+
+/// Replace the `{name}` placeholders of a template
+///
+/// The template is filled in a single pass, so placeholders that appear in
+/// the values, such as in the name of a company, are left alone.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result += &rest[..start];
+        rest = &rest[start..];
+        let value = values.iter().find(|&&(name, _)| {
+            rest[1..].starts_with(name) &&
+                rest[1 + name.len()..].starts_with('}')
+        });
+        match value {
+            Some(&(name, value)) => {
+                result += value;
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use tile;
+    use super::*;
+
+    #[test]
+    fn fill_placeholders_once() {
+        let values = [("a", "{b}"), ("b", "x")];
+        assert_eq!(fill("{a} {b} {c} {", &values), "{b} x {c} {");
+        assert_eq!(fill("function() { return {b}; }", &values),
+                   "function() { return x; }");
+    }
+
+    #[test]
+    fn placeholders_in_the_title() {
+        let definitions = tile::read_definitions(&::Options::new()).unwrap();
+        let game = game::Game::read(["games", "1830"].iter().collect(),
+                                    &definitions).unwrap();
+        let page = draw_viewer(&game, &::Options::new(), "{info} & {map}");
+        assert!(page.contains("<h1>{info} &amp; {map}</h1>"));
+        assert_eq!(page.matches("<svg").count(), 1);
+        assert_eq!(page.matches("var info = {\"").count(), 1);
+    }
+
+    #[test]
+    fn text_on_the_map_is_escaped() {
+        let definitions = tile::read_definitions(&::Options::new()).unwrap();
+        let mut game = game::Game::read(["games", "1830"].iter().collect(),
+                                        &definitions).unwrap();
+        let company = game.companies.remove("PRR").unwrap();
+        game.companies.insert(String::from("<b>R&R</b>"), company);
+        let page = draw_viewer(&game, &::Options::new(), "1830");
+        let map = &page[page.find("<svg").unwrap()..page.find("</svg>")
+                                                         .unwrap()];
+        assert!(!map.contains("<b>"));
+        assert!(map.contains("\n&lt;b&gt;R&amp;R&lt;/b&gt;\n</text>"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Converts tiles and games from other 18xx tools
+//!
+//! The converted tile definitions are written in the same layout as the
+//! definitions in `tiledefs/`, so they can be edited by hand afterwards.
+extern crate nalgebra as na;
+extern crate serde;
+extern crate serde_json;
+
+use std::fs;
+use std::path::Path as FilePath;
+use self::na::Vector2;
+use self::serde::Serialize;
+
+use schema;
+use tile;
+
+pub mod rails;
+pub mod tobymao;
+
+/// Position on a tile, either a position code or a hex-space coordinate
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum Position {
+    Named(&'static str),
+    HexSpace([f64; 3]),
+}
+
+impl Position {
+    /// Position of a point on the tile given in screen directions
+    ///
+    /// The x axis points to the right and the y axis points down, one unit
+    /// is the distance from the center of a tile to one of its corners.
+    pub fn from_point(point: Vector2<f64>) -> Position {
+        if point.norm() < 1e-6 {
+            return Position::Named("C");
+        }
+        // Express the point in the first two hex-space axes
+        let y = -point.y / (0.5 * 3.0_f64.sqrt());
+        let x = point.x - 0.5 * y;
+        let round = |v: f64| (v * 100.0).round() / 100.0 + 0.0;
+        Position::HexSpace([round(x), round(y), 0.0])
+    }
+}
+
+/// Screen direction of the middle of each edge, in the order of
+/// `tile::DIRECTIONS`
+pub fn edge_direction(direction: &str) -> Vector2<f64> {
+    let i = tile::DIRECTIONS.iter().position(|d| *d == direction).unwrap();
+    let angle = (i as f64 * 60.0 - 90.0).to_radians();
+    Vector2::new(angle.cos(), angle.sin())
+}
+
+#[derive(Serialize)]
+pub struct Text {
+    pub id: String,
+    pub position: Position,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub size: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub weight: Option<u32>,
+    pub anchor: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Path {
+    pub start: Position,
+    pub end: Position,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub track: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct City {
+    pub circles: u32,
+    pub text_id: String,
+    pub position: Position,
+    pub revenue_position: Position,
+}
+
+#[derive(Serialize)]
+pub struct Stop {
+    pub text_id: String,
+    pub revenue_angle: i32,
+    pub position: Position,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A tile definition as it is written to `tiledefs/`
+#[derive(Serialize)]
+pub struct Definition {
+    pub version: u32,
+    #[serde(skip_serializing_if="Vec::is_empty")]
+    pub text: Vec<Text>,
+    #[serde(skip_serializing_if="Vec::is_empty")]
+    pub paths: Vec<Path>,
+    #[serde(skip_serializing_if="Vec::is_empty")]
+    pub cities: Vec<City>,
+    #[serde(skip_serializing_if="Vec::is_empty")]
+    pub stops: Vec<Stop>,
+    #[serde(skip_serializing_if="is_false")]
+    pub is_lawson: bool,
+}
+
+impl Default for Definition {
+    fn default() -> Definition {
+        Definition {
+            version: schema::VERSION,
+            text: vec![],
+            paths: vec![],
+            cities: vec![],
+            stops: vec![],
+            is_lawson: false,
+        }
+    }
+}
+
+/// Write a tile definition to `<dir>/<name>.yaml`
+pub fn write_definition(dir: &str, name: &str, definition: &Definition,
+                        overwrite: bool) -> Result<(), String> {
+    write_file(&FilePath::new(dir).join(format!("{}.yaml", name)),
+               definition, overwrite)
+}
+
+/// Write a value to a file in the same layout as the files in `games/` and
+/// `tiledefs/`
+pub fn write_file<T: Serialize>(filename: &FilePath, value: &T,
+                                overwrite: bool) -> Result<(), String> {
+    if filename.exists() && !overwrite {
+        return Err(format!("{} already exists",
+                           filename.to_string_lossy()));
+    }
+    // Indent with tabs like the files that were written by hand
+    let mut text = Vec::new();
+    {
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
+        let mut serializer = serde_json::Serializer::with_formatter(
+            &mut text, formatter);
+        value.serialize(&mut serializer).unwrap();
+    }
+    let mut text = collapse_arrays(&String::from_utf8(text).unwrap());
+    text.push('\n');
+    fs::write(filename, text).map_err(|err| {
+        format!("Couldn't write {}: {}", filename.to_string_lossy(), err)
+    })
+}
+
+/// Put arrays of numbers on a single line, `[ 0.0, 0.6, 0.0 ]`
+fn collapse_arrays(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        result += &rest[..start];
+        rest = &rest[start..];
+        let end = rest.find(']').unwrap_or(rest.len() - 1);
+        let inner = &rest[1..end];
+        if inner.chars().all(|c| c.is_whitespace() || c == ',' || c == '.'
+                             || c == '-' || c.is_ascii_digit()) {
+            let numbers: Vec<_> = inner.split(',').map(|n| n.trim())
+                .collect();
+            result += &format!("[ {} ]", numbers.join(", "));
+            rest = &rest[end + 1..];
+        } else {
+            result.push('[');
+            rest = &rest[1..];
+        }
+    }
+    result + rest
+}
@@ -0,0 +1,754 @@
+//! Converts games from Rails, the Java program for playing 18xx games
+//!
+//! A game in Rails is described by a directory of XML files. `Map.xml` lists
+//! the hexes of the map, `Tiles.xml` the stations and track of every tile,
+//! including the preprinted tiles of the map which have an id of zero or
+//! less, and `TileSet.xml` how many of each tile there are. The companies
+//! are read from `CompanyManager.xml` when it is present.
+//!
+//! Tiles are converted by describing them in the tile language of
+//! 18xx.games, so their layout is approximated in the same way.
+extern crate nalgebra as na;
+extern crate roxmltree;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path as FilePath;
+use self::na::Vector2;
+use self::roxmltree::{Document, Node};
+
+use draw;
+use game::{Axis, Barrier, Company, Home, Location, Manifest, Map, MapTile,
+           Orientation, Parity};
+use tile;
+use tile::{Coordinate, RevenuePhase, RevenueTrack, Terrain, TerrainType,
+           TileDefinition, TileSpec};
+use super::{Definition, Position, Text};
+use super::tobymao;
+
+/// Position of the name of a city on a preprinted tile, below the center
+const NAME_POSITION: (f64, f64) = (0.0, 0.65);
+/// Position of terrain on a hex that also has stations, near the top
+const TERRAIN_POSITION: (f64, f64, f64) = (0.0, 0.35, 0.35);
+/// Terrain that costs at least this much is drawn as a mountain, cheaper
+/// terrain as a river
+const MOUNTAIN_COST: u32 = 100;
+
+/// Result of converting a game
+pub struct Imported {
+    pub map: Map,
+    pub manifest: Manifest,
+    pub companies: BTreeMap<String, Company>,
+    /// Tile definitions that don't exist yet, they belong in `tiledefs/`
+    pub definitions: Vec<(String, Definition)>,
+    /// Everything that has no equivalent in map18xx
+    pub untranslated: Vec<String>,
+}
+
+/// A tile from `Tiles.xml`, described in the tile language of 18xx.games
+struct RailsTile {
+    name: String,
+    color: Option<&'static str>,
+    dsl: String,
+    /// Whether the tile has no stations and no track
+    empty: bool,
+}
+
+/// A tile after it has been connected to a tile definition
+#[derive(Clone)]
+struct Converted {
+    definition: String,
+    color: Option<&'static str>,
+    text: Vec<(String, String)>,
+    /// Text ID for the name of a city, only present on preprinted tiles
+    name_id: Option<String>,
+}
+
+struct Importer<'a> {
+    game: &'a str,
+    known: &'a HashMap<String, TileDefinition>,
+    tiles: HashMap<String, RailsTile>,
+    converted: HashMap<String, Converted>,
+    definitions: Vec<(String, Definition)>,
+    untranslated: Vec<String>,
+}
+
+fn read(dir: &FilePath, name: &str) -> Result<String, String> {
+    fs::read_to_string(dir.join(name)).map_err(|err| {
+        format!("Couldn't read {}: {}", dir.join(name).to_string_lossy(),
+                err)
+    })
+}
+
+fn parse<'a>(text: &'a str, name: &str) -> Result<Document<'a>, String> {
+    Document::parse(text).map_err(|err| {
+        format!("Failed to parse {}: {}", name, err)
+    })
+}
+
+fn elements<'a, 'input>(node: Node<'a, 'input>, tag: &'a str)
+        -> impl Iterator<Item=Node<'a, 'input>> + 'a {
+    node.children().filter(move |n| n.has_tag_name(tag))
+}
+
+fn required<'a>(node: Node<'a, '_>, name: &str,
+                context: &str) -> Result<&'a str, String> {
+    node.attribute(name).ok_or_else(|| {
+        format!("{}: missing attribute '{}'", context, name)
+    })
+}
+
+/// Report the attributes and child elements of a node that aren't used
+fn check(node: Node, attributes: &[&str], children: &[&str], context: &str,
+         untranslated: &mut Vec<String>) {
+    for attribute in node.attributes() {
+        if !attributes.contains(&attribute.name()) {
+            untranslated.push(format!("{}: attribute {}=\"{}\"", context,
+                                      attribute.name(), attribute.value()));
+        }
+    }
+    for child in node.children().filter(|n| n.is_element()) {
+        if !children.contains(&child.tag_name().name()) {
+            untranslated.push(format!("{}: element <{}>", context,
+                                      child.tag_name().name()));
+        }
+    }
+}
+
+/// Read the tiles from `Tiles.xml`
+fn read_tiles(doc: &Document, untranslated: &mut Vec<String>)
+        -> Result<HashMap<String, RailsTile>, String> {
+    let mut tiles = HashMap::new();
+    for node in elements(doc.root_element(), "Tile") {
+        let id = required(node, "id", "Tiles.xml: <Tile>")?;
+        let context = format!("Tiles.xml tile {}", id);
+        check(node, &["id", "colour", "name"], &["Station", "Track"],
+              &context, untranslated);
+        let color = match node.attribute("colour").unwrap_or("white") {
+            "white" => None,
+            "yellow" => Some("yellow"),
+            "green" => Some("green"),
+            "brown" => Some("russet"),
+            "grey" | "gray" | "fixed" => Some("grey"),
+            "red" => Some("red"),
+            other => {
+                untranslated.push(format!("{}: colour {}", context, other));
+                None
+            }
+        };
+
+        let mut parts = vec![];
+        let mut stations = HashMap::new();
+        for station in elements(node, "Station") {
+            let station_id = required(station, "id", &context)?;
+            check(station, &["id", "type", "slots", "value", "position"],
+                  &[], &context, untranslated);
+            let mut params = vec![];
+            if let Some(value) = station.attribute("value")
+                    .and_then(|v| v.parse::<i32>().ok())
+                    .filter(|v| *v > 0) {
+                params.push(format!("revenue:{}", value));
+            }
+            let station_type = station.attribute("type").unwrap_or("City");
+            let part = match station_type {
+                "City" => {
+                    params.push(format!("slots:{}", station.attribute("slots")
+                                        .unwrap_or("1")));
+                    "city"
+                }
+                "Town" | "Halt" => "town",
+                "OffMapCity" => "offboard",
+                "Junction" => "junction",
+                other => {
+                    untranslated.push(format!("{}: station type {}, drawn \
+                                              as a city", context, other));
+                    "city"
+                }
+            };
+            stations.insert(station_id, parts.len());
+            parts.push(format!("{}={}", part, params.join(",")));
+        }
+        let mut tracks = vec![];
+        for track in elements(node, "Track") {
+            check(track, &["from", "to", "gauge"], &[], &context,
+                  untranslated);
+            let gauge = match track.attribute("gauge") {
+                None | Some("normal") => "",
+                Some("narrow") => ",track:narrow",
+                Some("dual") => ",track:dual",
+                Some(gauge) => {
+                    untranslated.push(format!("{}: {} gauge track", context,
+                                              gauge));
+                    ""
+                }
+            };
+            let end = |name: &str| -> Result<String, String> {
+                match name.strip_prefix("side") {
+                    Some(side) => match side.parse::<u32>() {
+                        Ok(side) if side < 6 => Ok(side.to_string()),
+                        _ => Err(format!("{}: invalid side '{}'", context,
+                                         name)),
+                    },
+                    None => stations.get(name)
+                        .map(|n| format!("_{}", n))
+                        .ok_or_else(|| format!("{}: unknown station '{}'",
+                                               context, name)),
+                }
+            };
+            tracks.push(format!("path=a:{},b:{}{}",
+                                end(required(track, "from", &context)?)?,
+                                end(required(track, "to", &context)?)?,
+                                gauge));
+        }
+        let empty = parts.is_empty() && tracks.is_empty();
+        parts.extend(tracks);
+        tiles.insert(id.to_string(), RailsTile {
+            name: node.attribute("name").unwrap_or(id).to_string(),
+            color,
+            dsl: parts.join(";"),
+            empty,
+        });
+    }
+    Ok(tiles)
+}
+
+impl<'a> Importer<'a> {
+    /// Connect a Rails tile to a tile definition
+    ///
+    /// Tiles that are laid during the game use the tile definition with the
+    /// same name when there is one. Other tiles are converted to a new tile
+    /// definition, the preprinted tiles are prefixed with the name of the
+    /// game because their ids are only unique within a game.
+    fn convert(&mut self, id: &str) -> Result<Converted, String> {
+        if let Some(converted) = self.converted.get(id) {
+            return Ok(converted.clone());
+        }
+        let (name, color, dsl, empty) = match self.tiles.get(id) {
+            Some(tile) => (tile.name.clone(), tile.color, tile.dsl.clone(),
+                           tile.empty),
+            None => return Err(format!("Tile {} is not in Tiles.xml", id)),
+        };
+        let preprinted = id.parse::<i32>().map(|i| i <= 0).unwrap_or(false);
+        let result = tobymao::convert(&dsl)
+            .map_err(|err| format!("Tile {}: {}", id, err))?;
+        // Off-boards are drawn as red tiles with a revenue track instead, so
+        // neither they nor the track that leads to them are reported
+        for part in result.untranslated.iter()
+                .filter(|p| !p.starts_with("offboard") &&
+                        !p.ends_with(tobymao::OFFBOARD_PATH)) {
+            self.untranslated.push(format!("Tiles.xml tile {}: {}", id,
+                                           part));
+        }
+
+        let converted = if preprinted && empty {
+            Converted { definition: String::from("plain"), color,
+                        text: vec![], name_id: None }
+        } else if preprinted {
+            let mut definition = result.definition;
+            let name_id = (result.text.len() + 1).to_string();
+            definition.text.push(Text {
+                id: name_id.clone(),
+                position: Position::from_point(
+                    Vector2::new(NAME_POSITION.0, NAME_POSITION.1)),
+                size: None,
+                weight: None,
+                anchor: "Middle",
+            });
+            let name = format!("{}-preprinted-{}", self.game,
+                               id.trim_start_matches('-'));
+            self.definitions.push((name.clone(), definition));
+            Converted { definition: name, color, text: result.text,
+                        name_id: Some(name_id) }
+        } else {
+            match self.known.get(&name) {
+                Some(known) => {
+                    // The text of the tile has to fit the definition
+                    let ids: HashSet<String> = known.cities().into_iter()
+                        .map(|c| c.text_id)
+                        .chain(known.stops().into_iter().map(|s| s.text_id))
+                        .collect();
+                    if result.text.iter().any(|(i, _)| !ids.contains(i)) {
+                        self.untranslated.push(format!(
+                            "Tiles.xml tile {}: the revenues don't match \
+                            tiledefs/{}.yaml, check the text of the tile",
+                            id, name));
+                    }
+                }
+                None => self.definitions.push((name.clone(),
+                                               result.definition)),
+            }
+            Converted { definition: name, color, text: result.text,
+                        name_id: None }
+        };
+        self.converted.insert(id.to_string(), converted.clone());
+        Ok(converted)
+    }
+
+    /// Convert the tiles in `TileSet.xml` that can be laid during the game
+    fn manifest(&mut self, doc: &Document) -> Result<Manifest, String> {
+        let mut manifest = Manifest::default();
+        let mut upgrades = 0;
+        for node in doc.root_element().descendants()
+                .filter(|n| n.has_tag_name("Tile")) {
+            let id = required(node, "id", "TileSet.xml: <Tile>")?;
+            let context = format!("TileSet.xml tile {}", id);
+            check(node, &["id", "quantity", "colour"], &["Upgrade"], &context,
+                  &mut self.untranslated);
+            if elements(node, "Upgrade").next().is_some() {
+                upgrades += 1;
+            }
+            let quantity = match node.attribute("quantity") {
+                // Tiles without a quantity are preprinted on the map
+                None => continue,
+                Some(quantity) => match quantity.parse::<u32>() {
+                    Ok(quantity) => quantity,
+                    Err(_) => {
+                        self.untranslated.push(format!("{}: quantity {}",
+                                                       context, quantity));
+                        continue;
+                    }
+                },
+            };
+            let converted = self.convert(id)?;
+            let mut text: HashMap<String, String> = converted.text
+                .into_iter().collect();
+            text.insert(String::from("number"), self.tiles[id].name.clone());
+            let color = converted.color.unwrap_or("ground").to_string();
+            manifest.add_tile(tile::Tile::new(converted.definition, color,
+                                              text),
+                              quantity);
+        }
+        if upgrades > 0 {
+            self.untranslated.push(format!("TileSet.xml: the upgrades of {} \
+                                           tiles", upgrades));
+        }
+        Ok(manifest)
+    }
+
+    /// Convert the hexes in `Map.xml`
+    fn map(&mut self, doc: &Document) -> Result<Map, String> {
+        let root = doc.root_element();
+        // The parity is guessed from the hex names instead of using `even`
+        check(root, &["tileOrientation", "letterOrientation"],
+              &["Hex"], "Map.xml", &mut self.untranslated);
+        let mut map = Map::default();
+        map.orientation = match root.attribute("tileOrientation") {
+            Some("EW") => Orientation::Vertical,
+            Some("NS") | None => Orientation::Horizontal,
+            Some(other) => return Err(format!("Map.xml: unknown tile \
+                                              orientation '{}'", other)),
+        };
+        let letters = match root.attribute("letterOrientation") {
+            Some("vertical") => Axis::Rows,
+            Some("horizontal") => Axis::Columns,
+            _ => map.coordinates.letters(&map.orientation),
+        };
+        if letters != map.coordinates.letters(&map.orientation) {
+            map.coordinates.letters = Some(letters);
+        }
+
+        // Use the parity for which the most hex names are valid
+        let hexes: Vec<Node> = elements(root, "Hex").collect();
+        let names = hexes.iter()
+            .map(|hex| required(*hex, "name", "Map.xml: <Hex>"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let invalid = |map: &Map| {
+            names.iter().filter(|n| Location::parse(n, map).is_err()).count()
+        };
+        let odd = invalid(&map);
+        map.coordinates.parity = Parity::Even;
+        if invalid(&map) >= odd {
+            map.coordinates.parity = Parity::Odd;
+        }
+
+        let mut barriers = HashSet::new();
+        for (hex, name) in hexes.into_iter().zip(names) {
+            let context = format!("Map.xml hex {}", name);
+            check(hex, &["name", "tile", "orientation", "value", "city",
+                         "cost", "impassable"],
+                  &[], &context, &mut self.untranslated);
+            let location = match Location::parse(name, &map) {
+                Ok(location) => location,
+                Err(err) => {
+                    self.untranslated.push(format!("{}: {}", context, err));
+                    continue;
+                }
+            };
+            let tile = self.map_tile(hex, name, &context)?;
+
+            for neighbour in hex.attribute("impassable").unwrap_or("")
+                    .split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+                let side = Location::parse(neighbour, &map).ok()
+                    .and_then(|n| draw::neighbour_side(location, n, &map)
+                              .map(|side| (n, side)));
+                match side {
+                    Some((n, side)) => {
+                        // Barriers are listed on both sides
+                        let pair = (location.min(n), location.max(n));
+                        if barriers.insert(pair) {
+                            map.barriers.push(Barrier {
+                                location: Location::Named(name.to_string()),
+                                side: side.to_string(),
+                            });
+                        }
+                    }
+                    None => self.untranslated.push(format!(
+                            "{}: impassable side to {}, which isn't a \
+                            neighbour", context, neighbour)),
+                }
+            }
+            map.add_tile(tile);
+        }
+        Ok(map)
+    }
+
+    fn map_tile(&mut self, hex: Node, name: &str,
+                context: &str) -> Result<MapTile, String> {
+        let id = hex.attribute("tile").unwrap_or("0");
+        let converted = if id == "0" && !self.tiles.contains_key(id) {
+            Converted { definition: String::from("plain"), color: None,
+                        text: vec![], name_id: None }
+        } else {
+            self.convert(id)?
+        };
+        let mut tile = MapTile::new(Location::Named(name.to_string()),
+                                    converted.definition);
+        tile.color = converted.color.map(|c| c.to_string());
+        tile.text = converted.text.iter().cloned().collect();
+
+        if let Some(orientation) = hex.attribute("orientation") {
+            match orientation.parse::<usize>() {
+                Ok(0) => {}
+                Ok(n) if n < tile::DIRECTIONS.len() =>
+                    tile.orientation = Some(tile::DIRECTIONS[n].to_string()),
+                _ => self.untranslated.push(format!("{}: orientation {}",
+                                                    context, orientation)),
+            }
+        }
+
+        // The value of a hex replaces the revenue of its stations, multiple
+        // values are the revenues of an off-board in each phase
+        if let Some(value) = hex.attribute("value") {
+            let values: Vec<&str> = value.split(',').map(|v| v.trim())
+                .collect();
+            let revenue_ids: Vec<String> = converted.text.iter()
+                .map(|(id, _)| id.clone())
+                .collect();
+            if values.len() == 1 && !revenue_ids.is_empty() {
+                for id in revenue_ids {
+                    tile.text.insert(id, value.to_string());
+                }
+            } else {
+                let colors = phase_colors(values.len());
+                let phases = values.iter().zip(colors)
+                    .map(|(value, color)| RevenuePhase {
+                        color: color.to_string(),
+                        value: value.to_string(),
+                        label: None,
+                    })
+                    .collect();
+                tile.revenue = Some(RevenueTrack::new(
+                        Coordinate::Named(String::from("C")), phases));
+            }
+        }
+
+        if let Some(city) = hex.attribute("city") {
+            match converted.name_id {
+                Some(ref id) => {
+                    tile.text.insert(id.clone(), city.to_string());
+                }
+                None => self.untranslated.push(format!("{}: city name {}",
+                                                       context, city)),
+            }
+        }
+
+        if let Some(cost) = hex.attribute("cost") {
+            let value: u32 = cost.parse().map_err(|_| {
+                format!("{}: invalid cost '{}'", context, cost)
+            })?;
+            let terrain_type = if value >= MOUNTAIN_COST {
+                TerrainType::Mountain
+            } else {
+                TerrainType::River
+            };
+            let position = if tile.tile == "plain" {
+                Coordinate::Named(String::from("C"))
+            } else {
+                Coordinate::HexSpace(TERRAIN_POSITION)
+            };
+            tile.terrain.push(Terrain::new(position, terrain_type,
+                                           cost.to_string()));
+        }
+        Ok(tile)
+    }
+
+    /// Convert the companies that have a colour in `CompanyManager.xml`
+    fn companies(&mut self, doc: &Document)
+            -> Result<BTreeMap<String, Company>, String> {
+        let mut companies = BTreeMap::new();
+        for node in doc.root_element().descendants()
+                .filter(|n| n.has_tag_name("Company")) {
+            let abbreviation = required(node, "name",
+                                        "CompanyManager.xml: <Company>")?;
+            let context = format!("CompanyManager.xml company {}",
+                                  abbreviation);
+            let color = match node.attribute("bgColour") {
+                Some(color) => color,
+                None => {
+                    self.untranslated.push(format!("{}: no colour, the \
+                                                   company is left out",
+                                                   context));
+                    continue;
+                }
+            };
+            let color = match closest_color(color) {
+                Some((name, exact)) => {
+                    if !exact {
+                        self.untranslated.push(format!(
+                            "{}: colour {} is drawn as {}", context, color,
+                            name));
+                    }
+                    name
+                }
+                None => return Err(format!("{}: invalid colour '{}'", context,
+                                           color)),
+            };
+            let home = match elements(node, "Home").next() {
+                None => None,
+                Some(home) => {
+                    let hex = required(home, "hex", &context)?;
+                    let location = Location::Named(hex.to_string());
+                    // Rails counts the cities on a tile starting at 1
+                    match home.attribute("city").map(|c| c.parse::<usize>()) {
+                        None => Some(Home::PositionOnly(location)),
+                        Some(Ok(city)) if city > 0 =>
+                            Some(Home::PositionStation(location, city - 1)),
+                        Some(_) => return Err(format!(
+                                "{}: invalid home city", context)),
+                    }
+                }
+            };
+            let name = node.attribute("longname").unwrap_or(abbreviation);
+            companies.insert(abbreviation.to_string(), Company {
+                name: name.to_string(),
+                color: color.to_string(),
+                home,
+            });
+        }
+        Ok(companies)
+    }
+}
+
+/// Colours of a revenue track, by the number of phases on the track
+fn phase_colors(phases: usize) -> Vec<&'static str> {
+    match phases {
+        1 => vec!["yellow"],
+        2 => vec!["yellow", "russet"],
+        3 => vec!["yellow", "green", "russet"],
+        _ => ["yellow", "green", "russet", "grey"].iter().cycle()
+            .take(phases).cloned().collect(),
+    }
+}
+
+fn parse_hex_color(color: &str) -> Option<[i32; 3]> {
+    let color = color.trim_start_matches('#');
+    if color.len() != 6 || !color.is_ascii() {
+        return None;
+    }
+    let mut rgb = [0; 3];
+    for (i, component) in rgb.iter_mut().enumerate() {
+        *component = i32::from_str_radix(&color[2 * i..2 * i + 2], 16)
+            .ok()?;
+    }
+    Some(rgb)
+}
+
+/// The named color that is closest to a color like `008000`, and whether
+/// they are the same
+fn closest_color(color: &str) -> Option<(&'static str, bool)> {
+    let rgb = parse_hex_color(color)?;
+    ["yellow", "green", "russet", "grey", "brown", "purple", "red", "blue",
+     "white"].iter()
+        .map(|name| {
+            let named = tile::colors::name_to_color(&name.to_string());
+            let other = parse_hex_color(named.value()).unwrap();
+            let distance: i32 = rgb.iter().zip(other.iter())
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            (*name, distance)
+        })
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(name, distance)| (name, distance == 0))
+}
+
+/// Convert the game in a directory of Rails XML files
+pub fn import(dir: &FilePath, game: &str,
+              known: &HashMap<String, TileDefinition>)
+        -> Result<Imported, String> {
+    let mut untranslated = vec![];
+    let tiles_text = read(dir, "Tiles.xml")?;
+    let tiles = read_tiles(&parse(&tiles_text, "Tiles.xml")?,
+                           &mut untranslated)?;
+    let mut importer = Importer {
+        game,
+        known,
+        tiles,
+        converted: HashMap::new(),
+        definitions: vec![],
+        untranslated,
+    };
+
+    let tile_set = read(dir, "TileSet.xml")?;
+    let manifest = importer.manifest(&parse(&tile_set, "TileSet.xml")?)?;
+    let map_text = read(dir, "Map.xml")?;
+    let map = importer.map(&parse(&map_text, "Map.xml")?)?;
+    let companies = if dir.join("CompanyManager.xml").exists() {
+        let text = read(dir, "CompanyManager.xml")?;
+        importer.companies(&parse(&text, "CompanyManager.xml")?)?
+    } else {
+        importer.untranslated.push(String::from(
+                "CompanyManager.xml doesn't exist, there are no companies"));
+        BTreeMap::new()
+    };
+
+    Ok(Imported {
+        map,
+        manifest,
+        companies,
+        definitions: importer.definitions,
+        untranslated: importer.untranslated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde;
+    extern crate serde_json;
+
+    use self::serde::Serialize;
+    use self::serde_json::Value;
+    use super::*;
+
+    /// Import the small game in `tests/rails`
+    fn import_fixture() -> Imported {
+        let known = tile::read_definitions(&::Options::new()).unwrap();
+        import(FilePath::new("tests/rails"), "sample", &known).unwrap()
+    }
+
+    fn value<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn read_tiles_xml() {
+        let doc = Document::parse(
+            "<Tiles><Tile id=\"57\" colour=\"yellow\" name=\"57\">\
+             <Station id=\"c\" type=\"City\" slots=\"2\" value=\"20\"/>\
+             <Track from=\"c\" to=\"side0\"/>\
+             <Track from=\"side1\" to=\"side4\" gauge=\"narrow\"/>\
+             </Tile><Tile id=\"0\" colour=\"white\"/></Tiles>").unwrap();
+        let mut untranslated = vec![];
+        let tiles = read_tiles(&doc, &mut untranslated).unwrap();
+        assert_eq!(tiles["57"].dsl, "city=revenue:20,slots:2;path=a:_0,b:0;\
+                                     path=a:1,b:4,track:narrow");
+        assert_eq!(tiles["57"].color, Some("yellow"));
+        assert!(!tiles["57"].empty);
+        assert!(tiles["0"].empty);
+        assert!(untranslated.is_empty());
+
+        let doc = Document::parse("<Tiles><Tile id=\"1\">\
+                                   <Track from=\"side6\" to=\"side0\"/>\
+                                   </Tile></Tiles>").unwrap();
+        assert_eq!(read_tiles(&doc, &mut untranslated).err(),
+                   Some(String::from("Tiles.xml tile 1: invalid side \
+                                     'side6'")));
+    }
+
+    #[test]
+    fn import_map() {
+        let imported = import_fixture();
+        let map = value(&imported.map);
+        assert_eq!(map["orientation"], "Vertical");
+        assert_eq!(map["coordinates"]["parity"], "odd");
+        // The barrier is listed on both hexes but only added once
+        assert_eq!(map["barriers"].as_array().unwrap().len(), 1);
+        assert_eq!(map["barriers"][0]["location"], "B2");
+
+        let tiles = map["tiles"].as_array().unwrap();
+        let hex = |location: &str| tiles.iter()
+            .find(|t| t["location"] == location).unwrap();
+        assert_eq!(hex("A1")["color"], "red");
+        assert_eq!(hex("A1")["revenue"]["phases"][1]["color"], "russet");
+        assert_eq!(hex("A1")["revenue"]["phases"][1]["value"], "50");
+        assert_eq!(hex("A3")["tile"], "plain");
+        assert_eq!(hex("A3")["terrain"][0]["type"], "mountain");
+        assert_eq!(hex("B4")["terrain"][0]["type"], "river");
+        assert_eq!(hex("B2")["tile"], "sample-preprinted-1");
+        assert_eq!(hex("B2")["text"]["1"], "20");
+        assert_eq!(hex("B2")["text"]["2"], "Springfield");
+    }
+
+    #[test]
+    fn import_tiles() {
+        let imported = import_fixture();
+        let manifest = value(&imported.manifest);
+        assert_eq!(manifest["amounts"]["57"], 4);
+        assert_eq!(manifest["amounts"]["8858"], 2);
+        assert_eq!(manifest["tiles"][0]["base_tile"], "57");
+        assert_eq!(manifest["tiles"][0]["text"]["1"], "20");
+
+        // Tile 57 already exists, the others are new
+        let mut names: Vec<_> = imported.definitions.iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["8858", "sample-preprinted-1",
+                               "sample-preprinted-2"]);
+        let offboard = imported.definitions.iter()
+            .find(|(name, _)| name == "sample-preprinted-2")
+            .map(|(_, definition)| value(definition))
+            .unwrap();
+        assert!(offboard.get("paths").is_none());
+    }
+
+    #[test]
+    fn import_companies() {
+        let imported = import_fixture();
+        let companies = value(&imported.companies);
+        assert_eq!(companies["SR"]["name"], "Springfield Railroad");
+        assert_eq!(companies["SR"]["color"], "green");
+        assert_eq!(companies["WR"]["color"], "blue");
+        assert!(companies.get("XR").is_none());
+        assert_eq!(value(&imported.companies["SR"].home),
+                   value(&Some(Home::PositionStation(
+                       Location::Named(String::from("B2")), 0))));
+    }
+
+    #[test]
+    fn untranslated() {
+        let imported = import_fixture();
+        assert_eq!(imported.untranslated, vec![
+            "Tiles.xml tile 8858: broad gauge track",
+            "TileSet.xml: the upgrades of 1 tiles",
+            "Map.xml: attribute even=\"A\"",
+            "CompanyManager.xml company WR: colour 0000F0 is drawn as blue",
+            "CompanyManager.xml company XR: no colour, the company is left \
+             out",
+        ]);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_hex_color("#00A550"), Some([0, 165, 80]));
+        assert_eq!(parse_hex_color("00a550"), Some([0, 165, 80]));
+        assert_eq!(parse_hex_color("00A55"), None);
+        assert_eq!(parse_hex_color("00A55G"), None);
+        assert_eq!(parse_hex_color("ééé"), None);
+        assert_eq!(parse_hex_color("0é000"), None);
+        assert_eq!(closest_color("00A550"), Some(("green", true)));
+        assert_eq!(closest_color("0000F0"), Some(("blue", false)));
+        assert_eq!(closest_color("ééé"), None);
+        assert_eq!(phase_colors(2), vec!["yellow", "russet"]);
+        assert_eq!(phase_colors(5).last(), Some(&"yellow"));
+    }
+}
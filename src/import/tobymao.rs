@@ -0,0 +1,383 @@
+//! Converts tiles written in the tile language of 18xx.games
+//!
+//! A tile consists of parts separated by `;`, each part is a type followed by
+//! `=` and a list of `key:value` parameters separated by `,`, for example
+//! `city=revenue:20;path=a:0,b:_0;path=a:_0,b:3`. Edges are numbered clockwise
+//! starting at the bottom edge. Paths refer to the cities, towns, offboards
+//! and junctions of a tile with `_0`, `_1`, etc. in the order they are listed.
+//!
+//! The layout of tiles is only approximated, cities and towns that share a
+//! tile are moved towards the edges that they connect to.
+extern crate nalgebra as na;
+
+use self::na::Vector2;
+
+use super::{City, Definition, Path, Position, Stop, Text, edge_direction};
+
+/// Names of the edges in the order that they are numbered
+const EDGES: [&str; 6] = ["S", "SW", "NW", "N", "NE", "SE"];
+
+/// Distance from the center at which cities and towns are placed when they
+/// don't fit in the center
+const NODE_DIST: f64 = 0.5;
+/// Distance between a city and its revenue
+const REVENUE_DIST: f64 = 0.45;
+/// Position of a label, near the top left corner
+const LABEL_POSITION: (f64, f64) = (-0.5, -0.4);
+/// Note added to paths that are left out because they lead to an offboard
+pub const OFFBOARD_PATH: &str = "(leads to an offboard, left out)";
+
+enum NodeType {
+    City(u32),
+    Town,
+    Offboard,
+    Junction,
+}
+
+struct Node {
+    node_type: NodeType,
+    revenue: Option<String>,
+    /// Edge that the node is placed towards
+    edge: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum End {
+    Edge(usize),
+    Node(usize),
+}
+
+/// Result of converting a single tile
+pub struct Converted {
+    pub definition: Definition,
+    /// Text for each text ID, such as revenues and labels, this belongs in
+    /// the manifest of a game
+    pub text: Vec<(String, String)>,
+    /// Parts of the tile that have no equivalent in a tile definition
+    pub untranslated: Vec<String>,
+}
+
+fn parse_edge(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(edge) if edge < EDGES.len() => Ok(edge),
+        _ => Err(format!("Invalid edge '{}'", value)),
+    }
+}
+
+fn parse_end(value: &str) -> Result<End, String> {
+    match value.strip_prefix('_') {
+        Some(node) => node.parse::<usize>()
+            .map(End::Node)
+            .map_err(|_| format!("Invalid node '{}'", value)),
+        None => parse_edge(value).map(End::Edge),
+    }
+}
+
+/// Convert a tile, such as `city=revenue:20;path=a:0,b:_0`
+pub fn convert(dsl: &str) -> Result<Converted, String> {
+    let mut nodes = vec![];
+    let mut paths = vec![];
+    let mut labels = vec![];
+    let mut untranslated = vec![];
+
+    for part in dsl.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let mut split = part.splitn(2, '=');
+        let part_type = split.next().unwrap();
+        let value = split.next().unwrap_or("");
+        let mut params = vec![];
+        for param in value.split(',').filter(|p| !p.is_empty()) {
+            let mut kv = param.splitn(2, ':');
+            params.push((kv.next().unwrap(), kv.next().unwrap_or("")));
+        }
+        let get = |key: &str| {
+            params.iter().find(|&&(k, _)| k == key).map(|&(_, v)| v)
+        };
+        let mut known: Vec<&str> = vec![];
+        match part_type {
+            "city" | "town" | "offboard" | "junction" => {
+                let node_type = match part_type {
+                    "city" => NodeType::City(match get("slots") {
+                        Some(slots) => slots.parse().map_err(|_| {
+                            format!("Invalid slots '{}'", slots)
+                        })?,
+                        None => 1,
+                    }),
+                    "town" => NodeType::Town,
+                    "offboard" => {
+                        untranslated.push(part.to_string());
+                        NodeType::Offboard
+                    }
+                    _ => NodeType::Junction,
+                };
+                let revenue = get("revenue").map(|r| r.to_string());
+                if revenue.as_ref().is_some_and(|r| r.contains('|')) {
+                    untranslated.push(format!("{} (phase revenues)", part));
+                }
+                let edge = match get("loc") {
+                    Some(loc) => Some(parse_edge(loc)?),
+                    None => None,
+                };
+                nodes.push(Node { node_type, revenue, edge });
+                known.extend(&["slots", "revenue", "loc"]);
+            }
+            "path" => {
+                let a = parse_end(get("a").ok_or("Path without 'a'")?)?;
+                let b = parse_end(get("b").ok_or("Path without 'b'")?)?;
+                let track = match get("track") {
+                    None | Some("broad") => None,
+                    Some("narrow") => Some("narrow"),
+                    Some("dual") => Some("dual"),
+                    Some(_) => {
+                        untranslated.push(format!("{} (track type)", part));
+                        None
+                    }
+                };
+                paths.push((a, b, track, part));
+                known.extend(&["a", "b", "track"]);
+            }
+            "label" => labels.push(value.to_string()),
+            _ => {
+                untranslated.push(part.to_string());
+                continue;
+            }
+        }
+        if part_type != "label" &&
+                params.iter().any(|&(k, _)| !known.contains(&k)) {
+            untranslated.push(format!("{} (only {} are used)", part,
+                                      known.join(", ")));
+        }
+    }
+
+    // Determine which edges each node connects to
+    let mut edges: Vec<Vec<usize>> = vec![vec![]; nodes.len()];
+    for &(a, b, _, _) in paths.iter() {
+        for &(from, to) in [(a, b), (b, a)].iter() {
+            if let End::Node(n) = from {
+                let node = edges.get_mut(n).ok_or_else(|| {
+                    format!("Path refers to unknown node _{}", n)
+                })?;
+                if let End::Edge(e) = to {
+                    node.push(e);
+                }
+            }
+        }
+    }
+
+    // A single city stays in the center, everything else moves towards the
+    // edges it connects to
+    let single = nodes.len() == 1;
+    let positions: Vec<Vector2<f64>> = nodes.iter().zip(edges.iter())
+        .map(|(node, edges)| {
+            let direction = match node.edge {
+                Some(e) => edge_direction(EDGES[e]),
+                None => edges.iter()
+                    .fold(Vector2::new(0.0, 0.0),
+                          |acc, e| acc + edge_direction(EDGES[*e])),
+            };
+            let centered = match node.node_type {
+                NodeType::Junction => true,
+                NodeType::City(_) => single && node.edge.is_none(),
+                _ => false,
+            };
+            if centered || direction.norm() < 1e-6 {
+                Vector2::new(0.0, 0.0)
+            } else {
+                direction.normalize() * NODE_DIST
+            }
+        })
+        .collect();
+
+    // Each distinct revenue gets its own text ID
+    let mut text: Vec<(String, String)> = vec![];
+    let text_id = |value: &str, text: &mut Vec<(String, String)>| {
+        match text.iter().find(|(_, v)| v == value) {
+            Some((id, _)) => id.clone(),
+            None => {
+                let id = (text.len() + 1).to_string();
+                text.push((id.clone(), value.to_string()));
+                id
+            }
+        }
+    };
+
+    let mut definition = Definition::default();
+    for (node, pos) in nodes.iter().zip(positions.iter()) {
+        let revenue_id = match (&node.node_type, &node.revenue) {
+            (NodeType::Offboard, _) | (_, None) => String::new(),
+            (_, Some(revenue)) => text_id(revenue, &mut text),
+        };
+        // Revenue goes beside the node, clockwise from the direction it is
+        // moved in
+        let side = if pos.norm() < 1e-6 {
+            Vector2::new(0.5, -0.866)
+        } else {
+            Vector2::new(-pos.y, pos.x).normalize()
+        };
+        match node.node_type {
+            NodeType::City(circles) => definition.cities.push(City {
+                circles,
+                text_id: revenue_id,
+                position: Position::from_point(*pos),
+                revenue_position: Position::from_point(
+                    pos + side * REVENUE_DIST),
+            }),
+            NodeType::Town => definition.stops.push(Stop {
+                text_id: revenue_id,
+                revenue_angle: side.y.atan2(side.x).to_degrees().round()
+                    as i32,
+                position: Position::from_point(*pos),
+            }),
+            NodeType::Junction => definition.is_lawson = true,
+            NodeType::Offboard => {}
+        }
+    }
+    for &(a, b, track, part) in paths.iter() {
+        // Offboards are drawn on the map with arrows, a path towards one
+        // would end at a point where nothing is drawn
+        let offboard = [a, b].iter().any(|end| match *end {
+            End::Node(n) => matches!(nodes[n].node_type, NodeType::Offboard),
+            End::Edge(_) => false,
+        });
+        if offboard {
+            untranslated.push(format!("{} {}", part, OFFBOARD_PATH));
+            continue;
+        }
+        let position = |end: End| match end {
+            End::Edge(e) => Position::Named(EDGES[e]),
+            End::Node(n) => Position::from_point(positions[n]),
+        };
+        definition.paths.push(Path { start: position(a),
+                                     end: position(b),
+                                     track });
+    }
+    for label in labels {
+        let id = (text.len() + 1).to_string();
+        text.push((id.clone(), label));
+        definition.text.push(Text {
+            id,
+            position: Position::from_point(Vector2::new(LABEL_POSITION.0,
+                                                        LABEL_POSITION.1)),
+            size: Some(String::from("120%")),
+            weight: Some(900),
+            anchor: "Middle",
+        });
+    }
+
+    Ok(Converted { definition, text, untranslated })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    fn position(position: &Position) -> String {
+        serde_json::to_string(position).unwrap()
+    }
+
+    /// Position of a node that is moved towards an edge
+    fn towards(edge: &str) -> String {
+        position(&Position::from_point(edge_direction(edge) * NODE_DIST))
+    }
+
+    fn text(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter()
+            .map(|&(id, value)| (id.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn city() {
+        let converted = convert("city=revenue:20,slots:2;path=a:0,b:_0;\
+                                 path=a:_0,b:3").unwrap();
+        let definition = converted.definition;
+        assert_eq!(definition.cities.len(), 1);
+        assert_eq!(definition.cities[0].circles, 2);
+        assert_eq!(definition.cities[0].text_id, "1");
+        assert_eq!(position(&definition.cities[0].position), "\"C\"");
+        let paths: Vec<_> = definition.paths.iter()
+            .map(|p| (position(&p.start), position(&p.end)))
+            .collect();
+        assert_eq!(paths, vec![("\"S\"".to_string(), "\"C\"".to_string()),
+                               ("\"C\"".to_string(), "\"N\"".to_string())]);
+        assert_eq!(converted.text, text(&[("1", "20")]));
+        assert!(converted.untranslated.is_empty());
+    }
+
+    #[test]
+    fn town() {
+        let converted = convert("town=revenue:10;path=a:0,b:_0").unwrap();
+        let definition = converted.definition;
+        assert!(definition.cities.is_empty());
+        assert_eq!(definition.stops.len(), 1);
+        assert_eq!(definition.stops[0].text_id, "1");
+        assert_eq!(position(&definition.stops[0].position), towards("S"));
+        assert_eq!(definition.stops[0].revenue_angle, 180);
+        assert_eq!(position(&definition.paths[0].end), towards("S"));
+        assert_eq!(converted.text, text(&[("1", "10")]));
+    }
+
+    #[test]
+    fn two_cities() {
+        let converted = convert("city=revenue:20;city=revenue:20;\
+                                 path=a:0,b:_0;path=a:_1,b:3;\
+                                 path=a:4,b:_1,track:narrow").unwrap();
+        let definition = converted.definition;
+        assert_eq!(definition.cities.len(), 2);
+        assert_eq!(position(&definition.cities[0].position), towards("S"));
+        assert_ne!(position(&definition.cities[1].position), "\"C\"");
+        // Both cities share the text of their revenue
+        assert_eq!(definition.cities[0].text_id, "1");
+        assert_eq!(definition.cities[1].text_id, "1");
+        assert_eq!(converted.text, text(&[("1", "20")]));
+        assert_eq!(definition.paths[2].track, Some("narrow"));
+    }
+
+    #[test]
+    fn labels() {
+        let converted = convert("city=revenue:30;path=a:0,b:_0;label=NY")
+            .unwrap();
+        assert_eq!(converted.text, text(&[("1", "30"), ("2", "NY")]));
+        assert_eq!(converted.definition.text.len(), 1);
+        assert_eq!(converted.definition.text[0].id, "2");
+        assert!(converted.untranslated.is_empty());
+    }
+
+    #[test]
+    fn phase_revenues() {
+        let converted = convert("city=revenue:yellow_20|brown_40;\
+                                 path=a:0,b:_0").unwrap();
+        assert_eq!(converted.text, text(&[("1", "yellow_20|brown_40")]));
+        assert_eq!(converted.untranslated,
+                   vec!["city=revenue:yellow_20|brown_40 (phase revenues)"]);
+    }
+
+    #[test]
+    fn offboard_paths_are_left_out() {
+        let converted = convert("offboard=revenue:yellow_30|brown_60;\
+                                 path=a:0,b:_0;path=a:1,b:_0").unwrap();
+        assert!(converted.definition.paths.is_empty());
+        assert!(converted.text.is_empty());
+        assert_eq!(converted.untranslated, vec![
+            "offboard=revenue:yellow_30|brown_60",
+            "offboard=revenue:yellow_30|brown_60 (phase revenues)",
+            "path=a:0,b:_0 (leads to an offboard, left out)",
+            "path=a:1,b:_0 (leads to an offboard, left out)",
+        ]);
+    }
+
+    #[test]
+    fn invalid_tiles() {
+        let error = |dsl: &str| convert(dsl).err().unwrap();
+        assert_eq!(error("path=a:0,b:_1"),
+                   "Path refers to unknown node _1");
+        assert_eq!(error("city=revenue:20;path=a:6,b:_0"),
+                   "Invalid edge '6'");
+        assert_eq!(error("city=revenue:20;path=a:0,b:_x"),
+                   "Invalid node '_x'");
+        assert_eq!(error("city=slots:two"), "Invalid slots 'two'");
+        assert_eq!(error("path=a:0"), "Path without 'b'");
+        assert_eq!(error("town=loc:7"), "Invalid edge '7'");
+    }
+}
@@ -1,20 +1,38 @@
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate serde_yaml;
 extern crate svg;
 
+use std::collections::HashMap;
 use std::fs;
 use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
+use tile::TileSpec;
 
 pub mod draw;
 pub mod game;
+pub mod html;
+pub mod import;
+pub mod render;
+pub mod schema;
+pub mod serve;
 pub mod tile;
+pub mod topology;
+pub mod watch;
 
 /// Place to store command line options
 pub struct Options {
     pub verbose: bool,
     pub debug_coordinates: bool,
+    /// Keep redrawing the output when the input changes
+    pub watch: bool,
+    /// Number of files to draw at the same time
+    pub jobs: usize,
+    /// Layers that are left out when drawing a map
+    pub hidden_layers: Vec<draw::Layer>,
 }
 
 impl Options {
@@ -22,10 +40,81 @@ impl Options {
         Options {
             verbose: false,
             debug_coordinates: false,
+            watch: false,
+            jobs: thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1),
+            hidden_layers: vec![],
         }
     }
 }
 
+/// The files that asset and state mode draw
+struct Outputs {
+    manifest: bool,
+    sheets: bool,
+    map: bool,
+}
+
+impl Outputs {
+    fn all() -> Outputs {
+        Outputs { manifest: true, sheets: true, map: true }
+    }
+
+    /// The outputs that are drawn from files that changed
+    ///
+    /// The manifest only affects the map of a game in progress, where the
+    /// tiles that are laid come from the manifest.
+    fn affected(changed: &[PathBuf], dir: &Path,
+                log: Option<&Path>) -> Outputs {
+        let mut outputs = Outputs { manifest: false, sheets: false,
+                                    map: false };
+        for file in changed {
+            if file.starts_with("tiledefs")
+                    || file.starts_with(dir.join("icons"))
+                    || *file == dir.join("map.yaml") {
+                return Outputs::all();
+            } else if *file == dir.join("manifest.yaml") {
+                outputs.manifest = true;
+                outputs.sheets = true;
+                outputs.map |= log.is_some();
+            } else if *file == dir.join("companies.yaml") {
+                outputs.map = true;
+            } else if Some(file.as_path()) == log {
+                outputs.manifest = true;
+                outputs.map = true;
+            }
+        }
+        outputs
+    }
+
+    fn any(&self) -> bool {
+        self.manifest || self.sheets || self.map
+    }
+}
+
+/// Save an SVG document
+fn save(filename: &str, document: &svg::Document) -> Result<(), String> {
+    svg::save(filename, document).map_err(|err| {
+        format!("Failed to write {}: {:?}", filename, err.kind())
+    })
+}
+
+/// Run a mode that draws files, and with `--watch` draw them again every
+/// time that the files they are drawn from change
+fn draw_and_watch<F>(options: &Options, paths: Vec<PathBuf>, mut draw: F)
+        where F: FnMut(&[PathBuf]) -> Result<(), String> {
+    if let Err(err) = draw(&[]) {
+        eprintln!("{}", err);
+        if !options.watch {
+            process::exit(1);
+        }
+    }
+    if options.watch {
+        watch::watch(paths, draw);
+    }
+}
+
 pub struct AssetOptions {
     pub name: String,
 }
@@ -66,62 +155,245 @@ impl StateOptions {
     }
 }
 
-pub fn definitions(options: &Options) {
-    let definitions = tile::definitions(options);
-    let document = svg::Document::new()
-        .set("width", "210mm") // A4 width
-        .set("height",
-             format!("{}mm", (definitions.len() as f64/4.0).ceil()*42.0+0.0))
-        .add(draw::draw_tile_definitions(&definitions));
-    svg::save("definitions.svg", &document).unwrap_or_else(|err| {
-        eprintln!("Failed to write definitions.svg: {:?}", err.kind());
-        process::exit(1);
-    });
+#[derive(Default)]
+pub struct ImportOptions {
+    pub name: String,
+    pub file: String,
 }
 
-pub fn asset_mode(options: &Options, asset_options: &AssetOptions) {
-    println!("Processing game '{}'", asset_options.name);
-    let definitions = tile::definitions(options);
-    let game = game::Game::load(["games", asset_options.name.as_str()]
-                                    .iter().collect(),
-                                &definitions);
+impl ImportOptions {
+    pub fn new() -> ImportOptions {
+        ImportOptions::default()
+    }
+}
 
-    println!("Exporting tile manifest...");
-    let document = svg::Document::new()
-        .set("width", "210mm") // A4 width
-        .set("height",
-             format!("{}mm",
-                     (game.manifest.tiles.len() as f64 / 4.0).ceil()
-                     * (game.map.scale * 10.0 + 3.0)))
-        .add(draw::draw_tile_manifest(&game));
-    svg::save(format!("{}-manifest.svg", asset_options.name), &document)
-        .unwrap_or_else(|err| {
-            eprintln!("Failed to write {}-manifest.svg: {:?}",
-                      asset_options.name, err.kind());
-            process::exit(1);
+pub struct ImportTilesOptions {
+    pub file: String,
+    pub output: String,
+    pub overwrite: bool,
+}
+
+impl ImportTilesOptions {
+    pub fn new() -> ImportTilesOptions {
+        ImportTilesOptions {
+            file: String::new(),
+            output: String::from("tiledefs"),
+            overwrite: false,
+        }
+    }
+}
+
+impl Default for ImportTilesOptions {
+    fn default() -> ImportTilesOptions {
+        ImportTilesOptions::new()
+    }
+}
+
+#[derive(Default)]
+pub struct ImportRailsOptions {
+    pub dir: String,
+    pub name: String,
+    pub overwrite: bool,
+}
+
+impl ImportRailsOptions {
+    pub fn new() -> ImportRailsOptions {
+        ImportRailsOptions::default()
+    }
+}
+
+#[derive(Default)]
+pub struct MigrateOptions {
+    pub files: Vec<String>,
+}
+
+impl MigrateOptions {
+    pub fn new() -> MigrateOptions {
+        MigrateOptions::default()
+    }
+}
+
+pub struct PreviewOptions {
+    pub name: String,
+    pub color: String,
+    /// Text to put on the tile, written as `ID=TEXT`
+    pub text: Vec<String>,
+    /// Game whose icons the tile can use
+    pub game: Option<String>,
+}
+
+impl PreviewOptions {
+    pub fn new() -> PreviewOptions {
+        PreviewOptions {
+            name: String::new(),
+            color: String::from("ground"),
+            text: vec![],
+            game: None,
+        }
+    }
+}
+
+impl Default for PreviewOptions {
+    fn default() -> PreviewOptions {
+        PreviewOptions::new()
+    }
+}
+
+pub struct ServeOptions {
+    pub names: Vec<String>,
+    pub address: String,
+}
+
+impl ServeOptions {
+    pub fn new() -> ServeOptions {
+        ServeOptions {
+            names: vec![],
+            address: String::from("127.0.0.1:8018"),
+        }
+    }
+}
+
+impl Default for ServeOptions {
+    fn default() -> ServeOptions {
+        ServeOptions::new()
+    }
+}
+
+pub fn definitions(options: &Options) {
+    draw_and_watch(options, vec![PathBuf::from("tiledefs")], |_| {
+        let definitions = tile::read_definitions(options)?;
+        let document = svg::Document::new()
+            .set("width", "210mm") // A4 width
+            .set("height",
+                 format!("{}mm",
+                         (definitions.len() as f64/4.0).ceil()*42.0+0.0))
+            .add(draw::draw_tile_definitions(&definitions));
+        save("definitions.svg", &document)
     });
+}
 
-    println!("Exporting tile sheets...");
-    let sheets = draw::draw_tile_sheets(&game);
-    for (i, sheet) in sheets.iter().enumerate() {
-        let filename = format!("{}-sheet-{}.svg", asset_options.name, i);
-        svg::save(filename, sheet).unwrap_or_else(|err| {
-            eprintln!("Failed to write {}-sheet-{}.svg: {:?}",
-                      asset_options.name, i, err.kind());
+/// Draw a single tile definition in every orientation
+pub fn preview_mode(options: &Options, preview_options: &PreviewOptions) {
+    let definitions = tile::definitions(options);
+    let definition = definitions.get(&preview_options.name)
+        .unwrap_or_else(|| {
+            eprintln!("There is no tile definition called {}",
+                      preview_options.name);
             process::exit(1);
         });
+    let color = tile::colors::name_to_color(&preview_options.color);
+    if tile::colors::color_to_name(&color).is_none() {
+        eprintln!("Unknown color {}", preview_options.color);
+        process::exit(1);
+    }
+
+    // Text that isn't given shows its ID
+    let mut text: HashMap<String, String> = definition.text_spec().iter()
+        .map(|t| (t.id.clone(), definition.get_text(&t.id).to_string()))
+        .collect();
+    for arg in preview_options.text.iter() {
+        let mut split = arg.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some(id), Some(value)) => {
+                text.insert(id.to_string(), value.to_string());
+            }
+            _ => {
+                eprintln!("Text should be given as ID=TEXT, not {}", arg);
+                process::exit(1);
+            }
+        }
+    }
+    let mut preview = tile::Tile::new(preview_options.name.clone(),
+                                      preview_options.color.clone(), text);
+    preview.set_definition(definition);
+
+    let icons = match preview_options.game {
+        Some(ref name) => {
+            let dir: PathBuf = ["games", name.as_str(), "icons"].iter()
+                .collect();
+            game::IconSymbol::read_all(dir).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            })
+        }
+        None => HashMap::new(),
+    };
+    for icon in preview.icons() {
+        if !icons.contains_key(&icon.name) {
+            eprintln!("Warning: icon {} is not defined, use --game to take \
+                      the icons of a game", icon.name);
+        }
     }
 
-    println!("Exporting map...");
-    let map_render = draw::draw_map(&game, &options);
-    svg::save(format!("{}-map.svg", asset_options.name), &map_render)
+    let filename = format!("{}-preview.svg", preview_options.name);
+    println!("Drawing {}...", filename);
+    save(&filename, &draw::draw_tile_preview(&preview, &icons))
         .unwrap_or_else(|err| {
-            eprintln!("Failed to write {}-map.svg: {:?}", asset_options.name,
-                      err.kind());
+            eprintln!("{}", err);
             process::exit(1);
+        });
+}
+
+pub fn asset_mode(options: &Options, asset_options: &AssetOptions) {
+    let name = asset_options.name.as_str();
+    let dir: PathBuf = ["games", name].iter().collect();
+    let paths = vec![PathBuf::from("tiledefs"), dir.clone()];
+    draw_and_watch(options, paths, |changed| {
+        let outputs = if changed.is_empty() {
+            Outputs::all()
+        } else {
+            Outputs::affected(changed, &dir, None)
+        };
+        if !outputs.any() {
+            return Ok(());
+        }
+        draw_assets(options, name, &outputs)
     });
 }
 
+/// Draw the files that are needed to play a game
+fn draw_assets(options: &Options, name: &str,
+               outputs: &Outputs) -> Result<(), String> {
+    println!("Processing game '{}'", name);
+    let definitions = tile::read_definitions(options)?;
+    let game = game::Game::read(["games", name].iter().collect(),
+                                &definitions)?;
+
+    let game = &game;
+    let mut jobs = vec![];
+    if outputs.manifest {
+        println!("Exporting tile manifest...");
+        jobs.push(render::Job::new(format!("{}-manifest.svg", name), move || {
+            svg::Document::new()
+                .set("width", "210mm") // A4 width
+                .set("height",
+                     format!("{}mm",
+                             (game.manifest.tiles.len() as f64 / 4.0).ceil()
+                             * (game.map.scale * 10.0 + 3.0)))
+                .set("xmlns:xlink", draw::XLINK_NS)
+                .add(draw::draw_icon_definitions(&game.icons))
+                .add(draw::draw_tile_manifest(game))
+        }));
+    }
+
+    if outputs.sheets {
+        println!("Exporting tile sheets...");
+        for i in 0..draw::tile_sheet_count(game) {
+            let filename = format!("{}-sheet-{}.svg", name, i);
+            jobs.push(render::Job::new(filename, move || {
+                draw::draw_tile_sheet(game, i)
+            }));
+        }
+    }
+
+    if outputs.map {
+        println!("Exporting map...");
+        jobs.push(render::Job::new(format!("{}-map.svg", name),
+                                   move || draw::draw_map(game, options)));
+    }
+    render::run(jobs, options.jobs)
+}
+
 pub fn newgame_mode(_options: &Options, newgame_options: &NewGameOptions) {
     // Validate that the game exists
     let games: Vec<String> = match fs::read_dir("games") {
@@ -160,33 +432,370 @@ pub fn newgame_mode(_options: &Options, newgame_options: &NewGameOptions) {
 }
 
 pub fn game_state_mode(options: &Options, state_options: &StateOptions) {
+    // The log tells which game to watch
     let log = game::Log::load(state_options, options);
-    let definitions = tile::definitions(options);
-    let game = game::Game::load(
+    let log_filename = PathBuf::from(format!("{}.yaml", state_options.name));
+    let dir: PathBuf = ["games", log.game_name.as_str()].iter().collect();
+    let paths = vec![PathBuf::from("tiledefs"), dir.clone(),
+                     log_filename.clone()];
+    draw_and_watch(options, paths, |changed| {
+        let outputs = if changed.is_empty() {
+            Outputs::all()
+        } else {
+            Outputs::affected(changed, &dir, Some(&log_filename))
+        };
+        if !outputs.any() {
+            return Ok(());
+        }
+        draw_state(options, state_options, &outputs)
+    });
+}
+
+/// Draw the manifest and map of a game in progress
+fn draw_state(options: &Options, state_options: &StateOptions,
+              outputs: &Outputs) -> Result<(), String> {
+    println!("Reading log from file...");
+    let log = game::Log::read(&format!("{}.yaml", state_options.name))?;
+    let definitions = tile::read_definitions(options)?;
+    let game = game::Game::read(
             ["games", log.game_name.as_str()].iter().collect(),
-            &definitions)
+            &definitions)?
         .set_log(log);
+    game.check_log()?;
+
+    let game = &game;
+    let mut jobs = vec![];
+    if outputs.manifest {
+        println!("Exporting tile manifest...");
+        jobs.push(render::Job::new(
+                format!("{}-manifest.svg", state_options.name),
+                move || state_manifest(game)));
+    }
+
+    if outputs.map {
+        println!("Exporting map...");
+        jobs.push(render::Job::new(format!("{}-map.svg", state_options.name),
+                                   move || draw::draw_map(game, options)));
+    }
+    render::run(jobs, options.jobs)
+}
 
-    println!("Exporting tile manifest...");
-    let document = svg::Document::new()
+/// Manifest showing the tiles that are left in a game in progress
+fn state_manifest(game: &game::Game) -> svg::Document {
+    svg::Document::new()
         .set("width", "210mm") // A4 width
         .set("height",
              format!("{}mm",
                      (game.manifest.tiles.len() as f64 / 3.0).ceil()*30.0+3.0))
-        .add(draw::draw_tile_manifest(&game));
-    svg::save(format!("{}-manifest.svg", state_options.name), &document)
+        .set("xmlns:xlink", draw::XLINK_NS)
+        .add(draw::draw_icon_definitions(&game.icons))
+        .add(draw::draw_tile_manifest(game))
+}
+
+pub fn html_mode(options: &Options, state_options: &StateOptions) {
+    let log = game::Log::load(state_options, options);
+    let definitions = tile::definitions(options);
+    let game = game::Game::load(
+            ["games", log.game_name.as_str()].iter().collect(),
+            &definitions)
+        .set_log(log);
+    game.check_log().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    println!("Exporting viewer...");
+    let page = html::draw_viewer(&game, options, &state_options.name);
+    let filename = format!("{}.html", state_options.name);
+    fs::write(&filename, page).unwrap_or_else(|err| {
+        eprintln!("Failed to write {}: {}", filename, err);
+        process::exit(1);
+    });
+}
+
+pub fn export_mode(options: &Options, state_options: &StateOptions) {
+    let log = game::Log::load(state_options, options);
+    let definitions = tile::definitions(options);
+    let game = game::Game::load(
+            ["games", log.game_name.as_str()].iter().collect(),
+            &definitions)
+        .set_log(log);
+    game.check_log().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    println!("Exporting game state...");
+    let filename = format!("{}-state.json", state_options.name);
+    let state = serde_json::to_string_pretty(&game.state()).unwrap();
+    fs::write(&filename, state).unwrap_or_else(|err| {
+        eprintln!("Failed to write {}: {}", filename, err);
+        process::exit(1);
+    });
+}
+
+pub fn import_mode(options: &Options, import_options: &ImportOptions) {
+    let mut state_options = StateOptions::new();
+    state_options.name = import_options.name.clone();
+    let log = game::Log::load(&state_options, options);
+    let definitions = tile::definitions(options);
+    let mut game = game::Game::load(
+            ["games", log.game_name.as_str()].iter().collect(),
+            &definitions)
+        .set_log(log);
+    game.check_log().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    println!("Reading actions from {}...", import_options.file);
+    let actions: Vec<game::Action> = fs::read_to_string(&import_options.file)
+        .map_err(|err| err.to_string())
+        .and_then(|text| serde_json::from_str(&text)
+                  .map_err(|err| err.to_string()))
         .unwrap_or_else(|err| {
-            eprintln!("Failed to write {}-manifest.svg: {}",
-                      state_options.name, err);
+            eprintln!("Failed to read actions from {}: {}",
+                      import_options.file, err);
             process::exit(1);
         });
+    // Check every action against the state it is applied to, nothing is
+    // written unless all of them are valid
+    for (i, action) in actions.into_iter().enumerate() {
+        if let Err(err) = game.validate(&action) {
+            eprintln!("Action {} is invalid: {}", i + 1, err);
+            process::exit(1);
+        }
+        game.log.as_mut().unwrap().push(action);
+    }
+    let filename = format!("{}.yaml", import_options.name);
+    println!("Writing to {}", filename);
+    game.log.unwrap().save(&filename).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+}
 
-    println!("Exporting map...");
-    let map_render = draw::draw_map(&game, &options);
-    svg::save(format!("{}-map.svg", state_options.name), &map_render)
+/// Convert tiles from the 18xx.games tile language to tile definitions
+///
+/// Every line of the input is a tile name followed by its tile, separated by
+/// `:` or `=>`. Quotes and trailing commas are ignored so that tile lists can
+/// be copied as they are.
+pub fn import_tiles_mode(_options: &Options,
+                         import_options: &ImportTilesOptions) {
+    let input = fs::read_to_string(&import_options.file)
         .unwrap_or_else(|err| {
-            eprintln!("Failed to write {}-map.svg: {}",
-                      state_options.name, err);
+            eprintln!("Couldn't read {}: {}", import_options.file, err);
             process::exit(1);
         });
+    let mut failed = false;
+    for line in input.lines().map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (name, dsl) = match line.find("=>").map(|i| (i, 2))
+                .or(line.find(':').map(|i| (i, 1))) {
+            Some((i, len)) => (&line[..i], &line[i + len..]),
+            None => {
+                eprintln!("Skipping line without a tile name: {}", line);
+                failed = true;
+                continue;
+            }
+        };
+        let strip: &[_] = &['\'', '"', ',', ' ', '\t'];
+        let name = name.trim_matches(strip);
+        let dsl = dsl.trim_matches(strip);
+        let converted = match import::tobymao::convert(dsl) {
+            Ok(converted) => converted,
+            Err(err) => {
+                eprintln!("{}: {}", name, err);
+                failed = true;
+                continue;
+            }
+        };
+        if let Err(err) = import::write_definition(&import_options.output,
+                                                   name,
+                                                   &converted.definition,
+                                                   import_options.overwrite) {
+            eprintln!("{}: {}", name, err);
+            failed = true;
+            continue;
+        }
+        let text: Vec<_> = converted.text.iter()
+            .map(|(id, value)| format!("\"{}\": \"{}\"", id, value))
+            .collect();
+        println!("{}: text {{{}}}", name, text.join(", "));
+        for part in converted.untranslated.iter() {
+            println!("{}: not translated: {}", name, part);
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Convert a game from the XML files of Rails
+///
+/// The game is written to `games/<name>/`, tile definitions that don't exist
+/// yet are written to `tiledefs/`. Nothing is written when one of the files
+/// already exists, unless it should be overwritten.
+pub fn import_rails_mode(options: &Options,
+                         import_options: &ImportRailsOptions) {
+    let definitions = tile::definitions(options);
+    println!("Converting Rails game in {}", import_options.dir);
+    let imported = import::rails::import(Path::new(&import_options.dir),
+                                         &import_options.name,
+                                         &definitions)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+
+    let game_dir: PathBuf = ["games", import_options.name.as_str()]
+        .iter().collect();
+    let tiledefs = Path::new("tiledefs");
+    let mut filenames: Vec<PathBuf> = ["map.yaml", "manifest.yaml",
+                                       "companies.yaml"].iter()
+        .map(|f| game_dir.join(f))
+        .collect();
+    filenames.extend(imported.definitions.iter()
+                     .map(|(name, _)| {
+                         tiledefs.join(format!("{}.yaml", name))
+                     }));
+    if !import_options.overwrite {
+        let existing: Vec<_> = filenames.iter().filter(|f| f.exists())
+            .collect();
+        if !existing.is_empty() {
+            for filename in existing {
+                eprintln!("{} already exists", filename.to_string_lossy());
+            }
+            process::exit(1);
+        }
+    }
+
+    fs::create_dir_all(&game_dir).unwrap_or_else(|err| {
+        eprintln!("Couldn't create {}: {}", game_dir.to_string_lossy(), err);
+        process::exit(1);
+    });
+    let results = vec![
+        import::write_file(&filenames[0], &imported.map, true),
+        import::write_file(&filenames[1], &imported.manifest, true),
+        import::write_file(&filenames[2], &imported.companies, true),
+    ].into_iter()
+        .chain(imported.definitions.iter().map(|(name, def)| {
+            println!("New tile definition {}", name);
+            import::write_definition("tiledefs", name, def, true)
+        }));
+    for result in results {
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+    for part in imported.untranslated.iter() {
+        println!("Not translated: {}", part);
+    }
+}
+
+/// Files that are migrated when no files are given, the tile definitions,
+/// the maps and manifests of all games and the logs in the working directory
+fn default_migrate_files() -> Vec<PathBuf> {
+    let list = |dir: &str| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|e| e.ok())
+                 .map(|e| e.path())
+                 .collect())
+            .unwrap_or_default()
+    };
+    let mut files: Vec<PathBuf> = list("tiledefs").into_iter()
+        .filter(|f| {
+            f.extension().is_some_and(|e| e == "yaml" || e == "toml")
+        })
+        .collect();
+    for game in list("games") {
+        files.push(game.join("map.yaml"));
+        files.push(game.join("manifest.yaml"));
+    }
+    files.extend(list(".").into_iter()
+                 .filter(|f| f.extension().is_some_and(|e| e == "yaml"))
+                 .filter(|f| schema::is_log(f)));
+    files.retain(|f| f.is_file());
+    files.sort();
+    files
+}
+
+/// Upgrade files to the current version of their format
+pub fn migrate_mode(options: &Options, migrate_options: &MigrateOptions) {
+    let files = if migrate_options.files.is_empty() {
+        default_migrate_files()
+    } else {
+        migrate_options.files.iter().map(PathBuf::from).collect()
+    };
+    let mut failed = false;
+    for file in files.iter() {
+        match schema::migrate(file) {
+            Ok(Some(version)) => println!("Upgraded {} from version {} to {}",
+                                          file.to_string_lossy(), version,
+                                          schema::VERSION),
+            Ok(None) => if options.verbose {
+                println!("{} is up to date", file.to_string_lossy());
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Describe how a tile has to be mirrored and rotated to look like another
+fn describe_transformation(rotation: usize, mirror: bool) -> String {
+    match (rotation, mirror) {
+        (0, false) => String::from("same"),
+        (0, true) => String::from("mirrored"),
+        (r, false) => format!("rotated to {}", tile::DIRECTIONS[r]),
+        (r, true) => format!("mirrored and rotated to {}",
+                             tile::DIRECTIONS[r]),
+    }
+}
+
+/// Report tile definitions that only differ by rotation or mirroring
+pub fn duplicates_mode(options: &Options) {
+    let definitions = tile::definitions(options);
+    let mut names: Vec<&String> = definitions.keys().collect();
+    names.sort();
+    let mut groups: Vec<(topology::Shape, Vec<&String>)> = vec![];
+    for name in names {
+        let shape = topology::canonical(&definitions[name]);
+        match groups.iter().position(|(s, _)| *s == shape) {
+            Some(i) => groups[i].1.push(name),
+            None => groups.push((shape, vec![name])),
+        }
+    }
+    groups.retain(|(_, names)| names.len() > 1);
+    if groups.is_empty() {
+        println!("No duplicates found");
+        return;
+    }
+    let mut failed = false;
+    for (_, names) in groups {
+        let first = &definitions[names[0]];
+        println!("{}:", names[0]);
+        for name in names.iter().skip(1) {
+            match topology::transformation(&definitions[*name], first) {
+                Some((rotation, mirror)) => {
+                    println!("    {} ({})", name,
+                             describe_transformation(rotation, mirror));
+                }
+                None => {
+                    eprintln!("{} has the same shape as {} but can't be \
+                              turned into it", name, names[0]);
+                    failed = true;
+                }
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
 }
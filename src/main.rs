@@ -5,6 +5,9 @@ extern crate map18xx;
 use clap::{App, Arg, SubCommand};
 
 fn main() {
+    let layers: Vec<&str> = map18xx::draw::Layer::ALL.iter()
+        .map(|layer| layer.name())
+        .collect();
     let matches = App::new("map18xx")
         .version(crate_version!())
         .author(crate_authors!())
@@ -19,6 +22,33 @@ fn main() {
              .short("c")
              .long("debug_coordinates")
              .global(true))
+        .arg(Arg::with_name("watch")
+             .help("Draw again when the input files change, in definitions, \
+                   asset and state mode. The files are checked twice a \
+                   second instead of using file system notifications")
+             .short("w")
+             .long("watch")
+             .global(true))
+        .arg(Arg::with_name("jobs")
+             .help("Number of files to draw at the same time, the number of \
+                   processors by default")
+             .short("j")
+             .long("jobs")
+             .takes_value(true)
+             .validator(|jobs| match jobs.parse::<usize>() {
+                 Ok(jobs) if jobs > 0 => Ok(()),
+                 _ => Err("must be a positive number".to_string()),
+             })
+             .global(true))
+        .arg(Arg::with_name("hide")
+             .help("Leave a layer out when drawing a map")
+             .long("hide")
+             .value_name("LAYER")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .possible_values(&layers)
+             .global(true))
         .subcommand(SubCommand::with_name("asset")
                     .about("Generate assets to PnP game")
                     .aliases(&["a", "assets"])
@@ -48,11 +78,116 @@ fn main() {
                          .help("Name of the game to generate the state for")
                          .required(true)
                          .index(1)))
+        .subcommand(SubCommand::with_name("export")
+                    .about("Write the state of a game in progress as JSON")
+                    .arg(Arg::with_name("name")
+                         .help("Name of the game to export")
+                         .required(true)
+                         .index(1)))
+        .subcommand(SubCommand::with_name("import")
+                    .about("Append a JSON list of actions to a game")
+                    .arg(Arg::with_name("name")
+                         .help("Name of the game to add the actions to")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("file")
+                         .help("JSON file with the actions")
+                         .required(true)
+                         .index(2)))
+        .subcommand(SubCommand::with_name("import-tiles")
+                    .about("Convert tiles from the 18xx.games tile language")
+                    .arg(Arg::with_name("file")
+                         .help("File with a tile name and tile on each line")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("output")
+                         .help("Directory to write the tile definitions to")
+                         .short("d")
+                         .long("output")
+                         .takes_value(true)
+                         .default_value("tiledefs"))
+                    .arg(Arg::with_name("overwrite")
+                         .help("Overwrite existing tile definitions")
+                         .short("o")
+                         .long("overwrite")))
+        .subcommand(SubCommand::with_name("import-rails")
+                    .about("Convert a game from the XML files of Rails")
+                    .arg(Arg::with_name("dir")
+                         .help("Directory with Map.xml, Tiles.xml and \
+                               TileSet.xml")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("name")
+                         .help("Name of the game to create")
+                         .required(true)
+                         .index(2))
+                    .arg(Arg::with_name("overwrite")
+                         .help("Overwrite existing files")
+                         .short("o")
+                         .long("overwrite")))
+        .subcommand(SubCommand::with_name("migrate")
+                    .about("Upgrade files to the current format")
+                    .arg(Arg::with_name("files")
+                         .help("Files to upgrade, all tile definitions, maps \
+                               and manifests and the logs of games in the \
+                               current directory by default")
+                         .multiple(true)
+                         .index(1)))
+        .subcommand(SubCommand::with_name("preview")
+                    .about("Draw a tile definition in every orientation")
+                    .arg(Arg::with_name("tiledef")
+                         .help("Name of the tile definition to draw")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("color")
+                         .help("Color of the tile")
+                         .long("color")
+                         .takes_value(true)
+                         .default_value("ground"))
+                    .arg(Arg::with_name("text")
+                         .help("Text to show for a text ID, as ID=TEXT")
+                         .long("text")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1))
+                    .arg(Arg::with_name("game")
+                         .help("Game whose icons the tile uses")
+                         .long("game")
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("duplicates")
+                    .about("List tile definitions that are the same up to \
+                            rotation or mirroring"))
+        .subcommand(SubCommand::with_name("serve")
+                    .about("Host games in progress over HTTP")
+                    .arg(Arg::with_name("address")
+                         .help("Address and port to listen on")
+                         .short("a")
+                         .long("address")
+                         .takes_value(true)
+                         .default_value("127.0.0.1:8018"))
+                    .arg(Arg::with_name("names")
+                         .help("Names of the games to host")
+                         .required(true)
+                         .multiple(true)
+                         .index(1)))
+        .subcommand(SubCommand::with_name("html")
+                    .about("Generate a web page showing a game in progress")
+                    .arg(Arg::with_name("name")
+                         .help("Name of the game to generate the page for")
+                         .required(true)
+                         .index(1)))
         .get_matches();
 
     let mut options = map18xx::Options::new();
     options.verbose = matches.is_present("verbose");
     options.debug_coordinates = matches.is_present("debug_coordinates");
+    options.watch = matches.is_present("watch");
+    if let Some(jobs) = matches.value_of("jobs") {
+        options.jobs = jobs.parse().unwrap();
+    }
+    options.hidden_layers = matches.values_of("hide")
+        .map(|layers| layers.map(|layer| layer.parse().unwrap()).collect())
+        .unwrap_or_default();
 
     // Determine subcommand
     match matches.subcommand() {
@@ -75,6 +210,74 @@ fn main() {
             state_options.name = matches.value_of("name").unwrap().to_string();
             map18xx::game_state_mode(&options, &state_options);
         }
+        ("html", Some(ref matches)) => {
+            let mut state_options = map18xx::StateOptions::new();
+            state_options.name = matches.value_of("name").unwrap().to_string();
+            map18xx::html_mode(&options, &state_options);
+        }
+        ("export", Some(ref matches)) => {
+            let mut state_options = map18xx::StateOptions::new();
+            state_options.name = matches.value_of("name").unwrap().to_string();
+            map18xx::export_mode(&options, &state_options);
+        }
+        ("import", Some(ref matches)) => {
+            let mut import_options = map18xx::ImportOptions::new();
+            import_options.name = matches.value_of("name").unwrap()
+                .to_string();
+            import_options.file = matches.value_of("file").unwrap()
+                .to_string();
+            map18xx::import_mode(&options, &import_options);
+        }
+        ("import-tiles", Some(ref matches)) => {
+            let mut import_options = map18xx::ImportTilesOptions::new();
+            import_options.file = matches.value_of("file").unwrap()
+                .to_string();
+            import_options.output = matches.value_of("output").unwrap()
+                .to_string();
+            import_options.overwrite = matches.is_present("overwrite");
+            map18xx::import_tiles_mode(&options, &import_options);
+        }
+        ("import-rails", Some(ref matches)) => {
+            let mut import_options = map18xx::ImportRailsOptions::new();
+            import_options.dir = matches.value_of("dir").unwrap()
+                .to_string();
+            import_options.name = matches.value_of("name").unwrap()
+                .to_string();
+            import_options.overwrite = matches.is_present("overwrite");
+            map18xx::import_rails_mode(&options, &import_options);
+        }
+        ("migrate", Some(ref matches)) => {
+            let mut migrate_options = map18xx::MigrateOptions::new();
+            migrate_options.files = matches.values_of("files")
+                .map(|files| files.map(|f| f.to_string()).collect())
+                .unwrap_or_default();
+            map18xx::migrate_mode(&options, &migrate_options);
+        }
+        ("preview", Some(ref matches)) => {
+            let mut preview_options = map18xx::PreviewOptions::new();
+            preview_options.name = matches.value_of("tiledef").unwrap()
+                .to_string();
+            preview_options.color = matches.value_of("color").unwrap()
+                .to_string();
+            preview_options.text = matches.values_of("text")
+                .map(|text| text.map(|t| t.to_string()).collect())
+                .unwrap_or_default();
+            preview_options.game = matches.value_of("game")
+                .map(|game| game.to_string());
+            map18xx::preview_mode(&options, &preview_options);
+        }
+        ("duplicates", Some(_)) => {
+            map18xx::duplicates_mode(&options);
+        }
+        ("serve", Some(ref matches)) => {
+            let mut serve_options = map18xx::ServeOptions::new();
+            serve_options.names = matches.values_of("names").unwrap()
+                .map(|name| name.to_string())
+                .collect();
+            serve_options.address = matches.value_of("address").unwrap()
+                .to_string();
+            map18xx::serve::serve(&options, &serve_options);
+        }
         ("", _) => map18xx::definitions(&options),
         (name, _) => eprintln!("Unkown subcommand {}.", name),
     }
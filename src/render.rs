@@ -0,0 +1,62 @@
+//! Drawing several files at the same time
+//!
+//! Every file is drawn into a string by one of the threads. The files are
+//! written afterwards in the order that the jobs were given, so the result
+//! is exactly the same as when they are drawn one after another.
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use svg;
+
+/// A file that still has to be drawn
+pub struct Job<'a> {
+    filename: String,
+    draw: Box<dyn FnOnce() -> svg::Document + Send + 'a>,
+}
+
+impl<'a> Job<'a> {
+    pub fn new<F>(filename: String, draw: F) -> Job<'a>
+            where F: FnOnce() -> svg::Document + Send + 'a {
+        Job { filename, draw: Box::new(draw) }
+    }
+}
+
+/// Draw jobs on `threads` threads and write the files that they draw
+///
+/// Nothing is written when drawing any of the files panics, so that watching
+/// files can go on.
+pub fn run(jobs: Vec<Job>, threads: usize) -> Result<(), String> {
+    let threads = threads.max(1).min(jobs.len());
+    let queue = Mutex::new(jobs.into_iter().enumerate());
+    let results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| {
+                let mut drawn = vec![];
+                loop {
+                    let next = queue.lock().unwrap().next();
+                    match next {
+                        Some((i, job)) => {
+                            let content = (job.draw)().to_string();
+                            drawn.push((i, job.filename, content));
+                        }
+                        None => return drawn,
+                    }
+                }
+            }))
+            .collect();
+        workers.into_iter().map(|worker| worker.join()).collect()
+    });
+    let mut drawn: Vec<(usize, String, String)> = vec![];
+    for result in results {
+        drawn.extend(result.map_err(|_| {
+            String::from("Failed to draw files")
+        })?);
+    }
+    drawn.sort_by_key(|&(i, _, _)| i);
+    for (_, filename, content) in drawn {
+        fs::write(&filename, content).map_err(|err| {
+            format!("Failed to write {}: {:?}", filename, err.kind())
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,483 @@
+//! Versions of the file formats
+//!
+//! Tile definitions, manifests, maps and logs have a `version` field that
+//! tells which version of the format they are written in. Files without it
+//! are version 1, the format from before the field was added. Every time a
+//! format changes `VERSION` goes up by one and a migration is added to
+//! `upgrade` that converts files from the previous version, so that
+//! `map18xx migrate` can bring old files up to date. Old files whose format
+//! didn't change are still read, with a warning.
+//!
+//! Version 0 are the tile definitions that were written in TOML. Version 2
+//! maps have a list of terrain on each hex and revenue tracks with a list of
+//! phases.
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
+
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use self::serde_json::{Map, Value};
+
+use import;
+
+/// Version of the files that are written by this version of map18xx
+pub const VERSION: u32 = 2;
+
+/// Version of files that don't have a version field
+pub const UNMARKED: u32 = 1;
+
+pub fn unmarked() -> u32 {
+    UNMARKED
+}
+
+/// The version of a file, files without one are `UNMARKED`
+fn version(value: &Value, filename: &str) -> Result<u32, String> {
+    match value.get("version") {
+        None => Ok(UNMARKED),
+        Some(version) => version.as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| format!("{} has an invalid version", filename)),
+    }
+}
+
+fn too_new(version: u32, filename: &str) -> String {
+    format!("{} is version {} of the format, this version of map18xx only \
+            understands up to version {}", filename, version, VERSION)
+}
+
+/// Check that a file can be read by this version of map18xx
+///
+/// A file in an older version is only rejected when upgrading it changes
+/// its contents, otherwise a warning is printed and it is read as it is.
+pub fn check(kind: Kind, value: &Value,
+             filename: &str) -> Result<(), String> {
+    let version = version(value, filename)?;
+    if version > VERSION {
+        return Err(too_new(version, filename));
+    } else if version == VERSION {
+        return Ok(());
+    }
+    let object = value.as_object()
+        .ok_or_else(|| format!("{} doesn't contain an object", filename))?;
+    let mut upgraded = object.clone();
+    let unchanged = (version..VERSION)
+        .all(|v| upgrade(kind, v, &mut upgraded).is_ok()) &&
+        upgraded == *object;
+    if !unchanged {
+        return Err(format!("{} is written in an old format, run `map18xx \
+                           migrate {}` to upgrade it", filename, filename));
+    }
+    eprintln!("Warning: {} is written in version {} of the format, run \
+              `map18xx migrate {}` to mark it as version {}", filename,
+              version, filename, VERSION);
+    Ok(())
+}
+
+/// Check the version of a YAML file, see `check`
+pub fn check_yaml(kind: Kind, text: &str,
+                  filename: &str) -> Result<(), String> {
+    let value: Value = serde_yaml::from_str(text).map_err(|err| {
+        format!("Failed to parse {}: {}", filename, err)
+    })?;
+    check(kind, &value, filename)
+}
+
+/// The kinds of files that have a version
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    TileDefinition,
+    Manifest,
+    Map,
+    Log,
+}
+
+impl Kind {
+    /// Recognise a file by the fields that only it has
+    fn detect(value: &Value) -> Result<Kind, String> {
+        let object = value.as_object()
+            .ok_or("it doesn't contain an object")?;
+        if object.contains_key("game_name") {
+            Ok(Kind::Log)
+        } else if object.contains_key("amounts") {
+            Ok(Kind::Manifest)
+        } else if object.contains_key("orientation") {
+            Ok(Kind::Map)
+        } else if object.keys().all(|k| ["version", "text", "paths",
+                                         "cities", "stops", "is_lawson",
+                                         "icons", "extends", "replace",
+                                         "template", "expand"]
+                                    .contains(&k.as_str())) {
+            Ok(Kind::TileDefinition)
+        } else {
+            Err(String::from("it isn't a tile definition, manifest, map or \
+                             log"))
+        }
+    }
+}
+
+/// Whether a file is the log of a game in progress
+pub fn is_log(filename: &Path) -> bool {
+    fs::read_to_string(filename).ok()
+        .and_then(|text| serde_yaml::from_str::<Value>(&text).ok())
+        .is_some_and(|value| Kind::detect(&value) == Ok(Kind::Log))
+}
+
+/// Rename a field of an object, if it is present
+fn rename(object: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = object.remove(from) {
+        object.insert(to.to_string(), value);
+    }
+}
+
+/// Apply a function to each object in an array field, and give the field a
+/// new name
+fn migrate_array<F>(object: &mut Map<String, Value>, from: &str, to: &str,
+                    mut f: F) -> Result<(), String>
+        where F: FnMut(&mut Map<String, Value>) -> Result<(), String> {
+    let mut array = match object.remove(from) {
+        Some(Value::Array(array)) => array,
+        Some(_) => return Err(format!("'{}' is not an array", from)),
+        None => return Ok(()),
+    };
+    for item in array.iter_mut() {
+        f(item.as_object_mut()
+          .ok_or_else(|| format!("'{}' contains a non-table", from))?)?;
+    }
+    if !array.is_empty() {
+        object.insert(to.to_string(), Value::Array(array));
+    }
+    Ok(())
+}
+
+/// Tile definitions in TOML used singular names for arrays and separate
+/// fields for named and hex-space positions
+fn tile_definition_0(object: &mut Map<String, Value>) -> Result<(), String> {
+    migrate_array(object, "path", "paths", |path| {
+        rename(path, "start_pos", "start");
+        rename(path, "end_pos", "end");
+        Ok(())
+    })?;
+    migrate_array(object, "city", "cities", |city| {
+        rename(city, "pos", "position");
+        if !city.contains_key("position") {
+            city.insert("position".to_string(), Value::from("C"));
+        }
+        rename(city, "revenue_pos", "revenue_position");
+        Ok(())
+    })?;
+    migrate_array(object, "stop", "stops", |stop| {
+        // The angle used to be measured in the other direction
+        let angle = stop.get("revenue_angle").and_then(|a| a.as_i64())
+            .ok_or("stop without a revenue_angle")?;
+        stop.insert("revenue_angle".to_string(), Value::from(-angle));
+        Ok(())
+    })
+}
+
+/// Maps had a single terrain object on a hex and a field for each color on
+/// revenue tracks
+///
+/// Files that are already in the new format are left alone, as they were
+/// written with version 1 for a while.
+fn map_1(object: &mut Map<String, Value>) -> Result<(), String> {
+    let tiles = match object.get_mut("tiles") {
+        Some(&mut Value::Array(ref mut tiles)) => tiles,
+        Some(_) => return Err(String::from("'tiles' is not an array")),
+        None => return Ok(()),
+    };
+    for tile in tiles.iter_mut() {
+        let tile = tile.as_object_mut()
+            .ok_or("'tiles' contains a non-table")?;
+        match tile.remove("terrain") {
+            Some(Value::Null) | None => {}
+            Some(terrain @ Value::Object(_)) => {
+                tile.insert("terrain".to_string(),
+                            Value::Array(vec![terrain]));
+            }
+            Some(terrain) => {
+                tile.insert("terrain".to_string(), terrain);
+            }
+        }
+        if let Some(revenue) = tile.get_mut("revenue") {
+            revenue_track_1(revenue.as_object_mut()
+                            .ok_or("'revenue' is not a table")?);
+        }
+    }
+    Ok(())
+}
+
+/// Turn the `yellow`, `green`, `russet` and `grey` fields of a revenue track
+/// into phases
+fn revenue_track_1(track: &mut Map<String, Value>) {
+    if track.contains_key("phases") {
+        return;
+    }
+    let mut phases = vec![];
+    for color in ["yellow", "green", "russet", "grey"].iter() {
+        let value = match track.remove(*color) {
+            Some(Value::Null) | None => continue,
+            Some(Value::Number(n)) => Value::from(n.to_string()),
+            Some(value) => value,
+        };
+        let mut phase = Map::new();
+        phase.insert("color".to_string(), Value::from(*color));
+        phase.insert("value".to_string(), value);
+        phases.push(Value::Object(phase));
+    }
+    track.insert("phases".to_string(), Value::Array(phases));
+}
+
+/// Upgrade a file from a version to the next version
+fn upgrade(kind: Kind, version: u32,
+           object: &mut Map<String, Value>) -> Result<(), String> {
+    match (kind, version) {
+        (Kind::TileDefinition, 0) => tile_definition_0(object),
+        (Kind::Map, 1) => map_1(object),
+        // Only maps changed in version 2
+        (_, 1) => Ok(()),
+        (kind, version) => Err(format!("there is no {:?} of version {}",
+                                       kind, version)),
+    }
+}
+
+/// Upgrade a file to the current version, in place
+///
+/// TOML tile definitions are replaced by a `.yaml` file with the same name.
+/// Returns the version that the file was in, or `None` when the file is
+/// already up to date.
+pub fn migrate(filename: &Path) -> Result<Option<u32>, String> {
+    let name = filename.to_string_lossy();
+    let text = fs::read_to_string(filename).map_err(|err| {
+        format!("Couldn't read {}: {}", name, err)
+    })?;
+    let is_toml = filename.extension().is_some_and(|e| e == "toml");
+    let value: Value = if is_toml {
+        toml::from_str(&text).map_err(|err| err.to_string())
+    } else {
+        serde_yaml::from_str(&text).map_err(|err| err.to_string())
+    }.map_err(|err| format!("Failed to parse {}: {}", name, err))?;
+    let (kind, version) = if is_toml {
+        (Kind::TileDefinition, 0)
+    } else {
+        let kind = Kind::detect(&value).map_err(|err| {
+            format!("Can't migrate {}: {}", name, err)
+        })?;
+        (kind, version(&value, &name)?)
+    };
+    if version > VERSION {
+        return Err(too_new(version, &name));
+    }
+    if version == VERSION {
+        return Ok(None);
+    }
+
+    let mut object = match value {
+        Value::Object(object) => object,
+        _ => return Err(format!("{} doesn't contain an object", name)),
+    };
+    for v in version..VERSION {
+        upgrade(kind, v, &mut object).map_err(|err| {
+            format!("Couldn't upgrade {} from version {}: {}", name, v, err)
+        })?;
+    }
+    // Put the version first so that it is easy to find
+    object.remove("version");
+    let mut upgraded = Map::new();
+    upgraded.insert("version".to_string(), Value::from(VERSION));
+    upgraded.extend(object);
+
+    let new_filename: PathBuf = filename.with_extension("yaml");
+    if kind == Kind::Log {
+        // Logs are written by map18xx itself, keep them in the same format
+        let text = serde_yaml::to_string(&upgraded).unwrap();
+        fs::write(&new_filename, text).map_err(|err| {
+            format!("Couldn't write {}: {}", name, err)
+        })?;
+    } else {
+        import::write_file(&new_filename, &upgraded, !is_toml)?;
+    }
+    if is_toml {
+        fs::remove_file(filename).map_err(|err| {
+            format!("Couldn't remove {}: {}", name, err)
+        })?;
+    }
+    Ok(Some(version))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+    extern crate serde_yaml;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use self::serde_json::Value;
+
+    use game;
+    use tile;
+    use super::*;
+
+    /// Write a file to a directory of its own so tests don't interfere
+    fn write(test: &str, name: &str, content: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join(format!("map18xx-schema-{}-{}", test, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        fs::write(&file, content).unwrap();
+        file
+    }
+
+    fn read(file: &PathBuf) -> String {
+        let text = fs::read_to_string(file).unwrap();
+        fs::remove_dir_all(file.parent().unwrap()).unwrap();
+        text
+    }
+
+    fn value(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn migrate_baseline_map() {
+        let file = write("map", "map.yaml", r#"{
+            "orientation": "Vertical",
+            "scale": 3.81,
+            "width": 2,
+            "height": 1,
+            "tiles": [
+                {"location": "A1", "color": "red",
+                    "revenue": {"position": "C", "yellow": "30",
+                                "russet": "50"}},
+                {"location": "A3", "terrain": {"position": "C",
+                    "type": "mountain", "cost": "$120"}}
+            ]
+        }"#);
+        assert_eq!(migrate(&file), Ok(Some(UNMARKED)));
+        let text = read(&file);
+        let map: Value = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(map["version"], VERSION);
+        assert_eq!(map["tiles"][0]["revenue"], value(r#"{
+            "position": "C",
+            "phases": [
+                {"color": "yellow", "value": "30"},
+                {"color": "russet", "value": "50"}
+            ]
+        }"#));
+        assert_eq!(map["tiles"][1]["terrain"], value(r#"[
+            {"position": "C", "type": "mountain", "cost": "$120"}
+        ]"#));
+        let map: game::Map = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(map.version, VERSION);
+    }
+
+    #[test]
+    fn migrate_current_map_marked_as_version_1() {
+        let file = write("marked", "map.yaml", r#"{
+            "version": 1,
+            "orientation": "Vertical",
+            "scale": 3.81,
+            "tiles": [
+                {"location": "A1",
+                    "revenue": {"position": "C", "phases": [
+                        {"color": "yellow", "value": "20"}]},
+                    "terrain": [{"position": "C", "type": "river",
+                                 "cost": "$40"}]}
+            ]
+        }"#);
+        assert_eq!(migrate(&file), Ok(Some(1)));
+        let map: Value = serde_yaml::from_str(&read(&file)).unwrap();
+        assert_eq!(map["tiles"][0]["revenue"]["phases"][0]["value"], "20");
+        assert_eq!(map["tiles"][0]["terrain"][0]["type"], "river");
+    }
+
+    #[test]
+    fn migrate_baseline_log() {
+        let file = write("log", "game.yaml", "---
+game_name: \"1830\"
+log:
+  - {type: tilelay, location: E19, tile: \"57\", orientation: N}
+  - {type: token, location: E19, company: Erie, city: ~}
+");
+        assert_eq!(migrate(&file), Ok(Some(UNMARKED)));
+        let log = game::Log::read(&file.to_string_lossy()).unwrap();
+        read(&file);
+        assert_eq!(log.version, VERSION);
+        assert_eq!(log.game_name, "1830");
+        assert_eq!(log.log.len(), 2);
+    }
+
+    #[test]
+    fn current_files_are_left_alone() {
+        let file = write("current", "game.yaml",
+                         &format!("---\nversion: {}\ngame_name: \"1830\"\n\
+                                  log: []\n", VERSION));
+        assert_eq!(migrate(&file), Ok(None));
+        read(&file);
+    }
+
+    #[test]
+    fn check_versions() {
+        let map = |version: &str| value(&format!(r#"{{
+            {}
+            "orientation": "Vertical",
+            "tiles": [{{"location": "A1",
+                        "revenue": {{"position": "C", "yellow": "30"}}}}]
+        }}"#, version));
+        assert!(check(Kind::Map, &map(r#""version": 2,"#), "map.yaml")
+                .is_ok());
+        assert_eq!(check(Kind::Map, &map(""), "map.yaml"),
+                   Err(String::from("map.yaml is written in an old format, \
+                                    run `map18xx migrate map.yaml` to \
+                                    upgrade it")));
+        assert_eq!(check(Kind::Map, &map(r#""version": 3,"#), "map.yaml"),
+                   Err(too_new(3, "map.yaml")));
+        assert_eq!(check(Kind::Map, &map(r#""version": 4294967298,"#),
+                         "map.yaml"),
+                   Err(String::from("map.yaml has an invalid version")));
+        assert_eq!(check(Kind::Map, &map(r#""version": -1,"#), "map.yaml"),
+                   Err(String::from("map.yaml has an invalid version")));
+        // Maps that are already in the current format are only marked with
+        // an old version, and logs didn't change at all
+        assert!(check(Kind::Map, &value(r#"{"orientation": "Vertical",
+                                            "tiles": []}"#),
+                      "map.yaml").is_ok());
+        assert!(check(Kind::Log, &value(r#"{"game_name": "1830",
+                                            "log": []}"#),
+                      "game.yaml").is_ok());
+    }
+
+    #[test]
+    fn read_unversioned_log_and_manifest() {
+        let file = write("unversioned", "game.yaml", "---
+game_name: \"1830\"
+log:
+  - {type: tilelay, location: E19, tile: \"57\", orientation: N}
+");
+        let log = game::Log::read(&file.to_string_lossy());
+        let manifest = fs::read_to_string("games/1830/manifest.yaml")
+            .unwrap()
+            .replacen("\t\"version\": 2,\n", "", 1);
+        assert!(!manifest.contains("version"));
+        let dir = file.parent().unwrap().join("1830");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("manifest.yaml"), manifest).unwrap();
+        for name in ["map.yaml", "companies.yaml"].iter() {
+            fs::copy(PathBuf::from("games/1830").join(name), dir.join(name))
+                .unwrap();
+        }
+        let definitions = tile::read_definitions(&::Options::new()).unwrap();
+        let game = game::Game::read(dir, &definitions);
+        read(&file);
+
+        let log = log.unwrap();
+        assert_eq!(log.version, VERSION);
+        assert_eq!(log.log.len(), 1);
+        let game = game.unwrap();
+        assert_eq!(game.manifest.version, VERSION);
+        assert!(!game.manifest.tiles.is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! Host games in progress over HTTP
+//!
+//! Every game is served under its own name, the name of its game file without
+//! the `.yaml` extension. The log is read from the game file on every request
+//! so the pages always show the current state of the game.
+//!
+//! * `GET /` lists the games that are served
+//! * `GET /<name>/map.svg` shows the map of a game
+//! * `GET /<name>/manifest.svg` shows the tiles that are left
+//! * `GET /<name>/log.json` gives the log of a game
+//! * `POST /<name>/actions` appends a single action in JSON to the log
+//!
+//! There is no authentication, anyone who can reach the server can add
+//! actions to the games. Every request is answered on its own, a request
+//! that fails is answered with an error and the server keeps running.
+extern crate serde_json;
+extern crate tiny_http;
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use self::tiny_http::{Header, Method, Request, Response, Server};
+
+use draw;
+use game;
+use tile;
+
+/// Largest request body that is accepted, actions are much smaller
+const MAX_BODY: u64 = 64 * 1024;
+
+/// A game that is being served together with the file its log is in
+struct Served {
+    game: game::Game,
+    filename: String,
+}
+
+impl Served {
+    /// Read the log again in case the game file was changed
+    fn reload(&mut self) -> Result<(), String> {
+        self.game.log = Some(game::Log::read(&self.filename)?);
+        self.game.check_log().map_err(|err| {
+            format!("{}: {}", self.filename, err)
+        })
+    }
+}
+
+fn response(status: u32, content_type: &str,
+            body: String) -> Response<Cursor<Vec<u8>>> {
+    let header = Header::from_bytes(&b"Content-Type"[..],
+                                    content_type.as_bytes()).unwrap();
+    Response::from_data(body.into_bytes())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn error(status: u32, message: &str) -> Response<Cursor<Vec<u8>>> {
+    response(status, "text/plain; charset=utf-8", format!("{}\n", message))
+}
+
+/// Decode the `%XX` escapes in part of a URL
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text.get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("Invalid escape in '{}'", text))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|_| format!("'{}' is not valid UTF-8", text))
+}
+
+/// Escape everything but letters, digits and `-._~` for use in a URL
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            (b as char).to_string()
+        } else {
+            format!("%{:02X}", b)
+        })
+        .collect()
+}
+
+/// Page with links to each of the served games
+fn index(games: &HashMap<String, Served>) -> Response<Cursor<Vec<u8>>> {
+    let mut names: Vec<_> = games.keys().collect();
+    names.sort();
+    let mut page = String::from("<!DOCTYPE html>\n<html>\n<head>\n\
+                                 <meta charset=\"utf-8\">\n\
+                                 <title>map18xx</title>\n</head>\n<body>\n\
+                                 <ul>\n");
+    for name in names {
+        page += &format!("<li>{}: <a href=\"/{1}/map.svg\">map</a>, \
+                          <a href=\"/{1}/manifest.svg\">manifest</a>, \
+                          <a href=\"/{1}/log.json\">log</a></li>\n",
+                         draw::escape(name), percent_encode(name));
+    }
+    page += "</ul>\n</body>\n</html>\n";
+    response(200, "text/html; charset=utf-8", page)
+}
+
+/// Validate an action and append it to the log of a game
+fn add_action(request: &mut Request,
+              served: &mut Served) -> Response<Cursor<Vec<u8>>> {
+    let mut body = String::new();
+    if let Err(err) = request.as_reader().take(MAX_BODY)
+            .read_to_string(&mut body) {
+        return error(400, &format!("Couldn't read action: {}", err));
+    }
+    let action: game::Action = match serde_json::from_str(&body) {
+        Ok(action) => action,
+        Err(err) => return error(400, &format!("Invalid action: {}", err)),
+    };
+    if let Err(err) = served.game.validate(&action) {
+        return error(400, &err);
+    }
+    println!("{}: {}", served.filename, action.describe(&served.game.map));
+    let log = served.game.log.as_mut().unwrap();
+    log.push(action);
+    if let Err(err) = log.save(&served.filename) {
+        eprintln!("{}", err);
+        return error(500, &err);
+    }
+    response(200, "application/json", serde_json::to_string(log).unwrap())
+}
+
+fn respond(request: &mut Request,
+           games: &mut HashMap<String, Served>,
+           options: &::Options) -> Response<Cursor<Vec<u8>>> {
+    let method = request.method().clone();
+    let url = request.url().split('?').next().unwrap().to_string();
+    let parts: Vec<&str> = url.trim_matches('/').split('/').collect();
+    let (name, page) = match (&method, parts.as_slice()) {
+        (&Method::Get, &[""]) => return index(games),
+        (_, &[name, page]) => (name, page),
+        _ => return error(404, "Not found"),
+    };
+    let name = match percent_decode(name) {
+        Ok(name) => name,
+        Err(err) => return error(400, &err),
+    };
+    let served = match games.get_mut(&name) {
+        Some(served) => served,
+        None => return error(404, &format!("Unknown game {}", name)),
+    };
+    if let Err(err) = served.reload() {
+        eprintln!("{}", err);
+        return error(500, &err);
+    }
+    match (&method, page) {
+        (&Method::Get, "map.svg") =>
+            response(200, "image/svg+xml",
+                     draw::draw_map(&served.game, options).to_string()),
+        (&Method::Get, "manifest.svg") =>
+            response(200, "image/svg+xml",
+                     ::state_manifest(&served.game).to_string()),
+        (&Method::Get, "log.json") =>
+            response(200, "application/json",
+                     serde_json::to_string(&served.game.log).unwrap()),
+        (&Method::Post, "actions") => add_action(request, served),
+        _ => error(404, "Not found"),
+    }
+}
+
+pub fn serve(options: &::Options, serve_options: &::ServeOptions) {
+    let definitions = tile::definitions(options);
+    let mut games = HashMap::new();
+    for name in serve_options.names.iter() {
+        let filename = format!("{}.yaml", name);
+        let log = game::Log::read(&filename).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        let game = game::Game::load(
+                ["games", log.game_name.as_str()].iter().collect(),
+                &definitions);
+        games.insert(name.clone(), Served { game, filename });
+    }
+
+    let server = Server::http(serve_options.address.as_str())
+        .unwrap_or_else(|err| {
+            eprintln!("Couldn't listen on {}: {}", serve_options.address,
+                      err);
+            process::exit(1);
+        });
+    println!("Serving games on http://{}/", serve_options.address);
+    for mut request in server.incoming_requests() {
+        // A bug in handling one request must not take the other games down,
+        // the game is read again on the next request
+        let response = panic::catch_unwind(AssertUnwindSafe(|| {
+            respond(&mut request, &mut games, options)
+        })).unwrap_or_else(|_| error(500, "Internal error"));
+        if let Err(err) = request.respond(response) {
+            eprintln!("Failed to send response: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_names() {
+        assert_eq!(percent_decode("game"), Ok(String::from("game")));
+        assert_eq!(percent_decode("my%20game"), Ok(String::from("my game")));
+        assert_eq!(percent_decode("%C3%A9t%c3%a9"), Ok(String::from("été")));
+        assert!(percent_decode("100%").is_err());
+        assert!(percent_decode("%4").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%%41").is_err());
+        assert!(percent_decode("%C3").is_err());
+    }
+
+    #[test]
+    fn encode_names() {
+        assert_eq!(percent_encode("1830-game_2.~"), "1830-game_2.~");
+        assert_eq!(percent_encode("my game/été"), "my%20game%2F%C3%A9t%C3%A9");
+        for name in ["my game", "B&O", "100%", "été"].iter() {
+            assert_eq!(percent_decode(&percent_encode(name)),
+                       Ok(name.to_string()));
+        }
+    }
+}
@@ -22,14 +22,20 @@
 //! ![Coordinate system](../../../../axes.svg)
 
 extern crate nalgebra as na;
+extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f64::consts::PI;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path as FilePath, PathBuf};
 use std::fs::File;
 use std::process;
+use self::serde_json::{Map, Value};
+
+use schema;
+use topology;
 
 /// Standard colors that can be used
 pub mod colors {
@@ -55,8 +61,10 @@ pub mod colors {
     pub const RUSSET:  Color  = Color { value: "#CD7F32" }; // Bronze
     pub const GREY:    Color  = Color { value: "#ACACAC" }; // Silver Chalice
     pub const BROWN:   Color  = Color { value: "#7B3F00" }; // Chocolate
+    pub const PURPLE:  Color  = Color { value: "#9F00C5" }; // Purple (Munsell)
     pub const RED:     Color  = Color { value: "#DC143C" }; // Crimson
     pub const BLUE:    Color  = Color { value: "#007FFF" }; // Azure
+    pub const SEA:     Color  = Color { value: "#A4DDED" }; // Non-photo Blue
     pub const BARRIER: Color  = Color { value: "#660000" }; // Blood Red
     pub const WHITE:   Color  = Color { value: "#FFFFFF" };
 
@@ -68,13 +76,23 @@ pub mod colors {
             "russet"  => RUSSET,
             "grey"    => GREY,
             "brown"   => BROWN,
+            "purple"  => PURPLE,
             "red"     => RED,
             "blue"    => BLUE,
+            "sea"     => SEA,
             "barrier" => BARRIER,
             "white"   => WHITE,
             _         => Color { value: "#000000" },
         }
     }
+
+    /// Name of a standard color, the inverse of `name_to_color`
+    pub fn color_to_name(color: &Color) -> Option<&'static str> {
+        ["ground", "yellow", "green", "russet", "grey", "brown", "purple",
+         "red", "blue", "sea", "barrier", "white"].iter()
+            .find(|name| name_to_color(&name.to_string()).value == color.value)
+            .cloned()
+    }
 }
 
 /// Converts a position code to hex coordinates
@@ -84,7 +102,7 @@ pub mod colors {
 ///
 /// # Panics
 ///
-/// On invalid position code
+/// On invalid position code, these are rejected when coordinates are read
 fn edge_to_coordinate(edge: &str) -> na::Vector3<f64> {
     match edge {
         "N"  => na::Vector3::new( 0.0,  0.5,  0.5),
@@ -99,6 +117,11 @@ fn edge_to_coordinate(edge: &str) -> na::Vector3<f64> {
 }
 
 /// Converts a compass direction to a number of degrees of rotation
+///
+/// # Panics
+///
+/// On invalid direction, use `check_direction` on directions that are read
+/// from files
 pub fn direction_to_angle(direction: &str) -> f64 {
     match direction {
         "N"  => 0.0,
@@ -111,14 +134,66 @@ pub fn direction_to_angle(direction: &str) -> f64 {
     }
 }
 
+/// Names of the directions a tile can face, clockwise from north
+pub const DIRECTIONS: [&str; 6] = ["N", "NE", "SE", "S", "SW", "NW"];
+
+/// Check that a direction read from a file is one of `DIRECTIONS`
+pub fn check_direction(direction: &str) -> Result<(), String> {
+    if DIRECTIONS.contains(&direction) {
+        Ok(())
+    } else {
+        Err(format!("Invalid direction '{}', expected one of {}", direction,
+                    DIRECTIONS.join(", ")))
+    }
+}
+
+/// Converts an angle to the index in `DIRECTIONS` of the closest direction
+pub fn angle_to_rotation(angle: f64) -> usize {
+    let sixths = (angle / (PI / 3.0)).round() as i64;
+    (((sixths % 6) + 6) % 6) as usize
+}
+
+/// Converts an angle back to the closest direction name
+pub fn angle_to_direction(angle: f64) -> &'static str {
+    DIRECTIONS[angle_to_rotation(angle)]
+}
+
 /// Represents named or hex space coordinate
-#[derive(Clone, Deserialize, Debug)]
+#[derive(Clone, Serialize, Debug)]
 #[serde(untagged)]
 pub enum Coordinate {
     Named(String),
     HexSpace((f64, f64, f64)),
 }
 
+impl<'de> serde::Deserialize<'de> for Coordinate {
+    /// Position codes are checked here so that drawing can't fail on them
+    fn deserialize<D>(deserializer: D) -> Result<Coordinate, D::Error>
+            where D: serde::Deserializer<'de> {
+        use self::serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Named(String),
+            HexSpace((f64, f64, f64)),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Named(name) => {
+                if name == "C" || DIRECTIONS.contains(&name.as_str()) {
+                    Ok(Coordinate::Named(name))
+                } else {
+                    Err(D::Error::custom(format!(
+                        "invalid position code '{}', expected C or one of {}",
+                        name, DIRECTIONS.join(", "))))
+                }
+            }
+            Raw::HexSpace(pos) => Ok(Coordinate::HexSpace(pos)),
+        }
+    }
+}
+
 impl Coordinate {
     pub fn as_vector(&self) -> na::Vector3<f64> {
         match self {
@@ -146,7 +221,10 @@ pub trait TileSpec {
     fn arrows(&self) -> Vec<Coordinate> { vec![] }
     /// Revenue track on the tile
     fn revenue_track(&self) -> Option<RevenueTrack> { None }
-    fn terrain(&self) -> Option<Terrain> { None }
+    /// Terrain features on the tile
+    fn terrain(&self) -> Vec<Terrain> { vec![] }
+    /// Icons that are placed on the tile
+    fn icons(&self) -> Vec<Icon>;
 
     fn get_text<'a>(&'a self, &'a str) -> &'a str;
     fn text_position(&self, usize) -> Option<na::Vector3<f64>>;
@@ -154,13 +232,27 @@ pub trait TileSpec {
 
     /// Rotation of the tile
     fn orientation(&self) -> f64 { 0.0 }
+
+    /// Which edges, cities and stops are linked by the track on the tile,
+    /// with the edges turned to the orientation of the tile
+    fn topology(&self) -> topology::Topology {
+        topology::derive(self)
+            .transformed(angle_to_rotation(self.orientation()), false)
+    }
+    /// Edges that track leaves the tile through, as indices into `DIRECTIONS`
+    fn exits(&self) -> Vec<usize> { self.topology().exits }
+
+    /// Identifies what the tile looks like, tiles with the same key are
+    /// drawn the same way. `None` when the drawing cannot be shared.
+    fn drawing_key(&self) -> Option<String> { None }
 }
 
 /// The specification of a tile to be used in the game
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Tile {
     base_tile: String,
     color: String,
+    #[serde(serialize_with="serialize_sorted")]
     text: HashMap<String, String>,
 
     #[serde(skip)]
@@ -168,6 +260,11 @@ pub struct Tile {
 }
 
 impl Tile {
+    pub fn new(base_tile: String, color: String,
+               text: HashMap<String, String>) -> Tile {
+        Tile { base_tile, color, text, definition: None }
+    }
+
     pub fn set_definition(&mut self, definition: &TileDefinition) {
         self.definition = Some(definition.clone());
     }
@@ -202,6 +299,10 @@ impl TileSpec for Tile {
         self.text.insert("number".to_string(), name);
     }
 
+    fn drawing_key(&self) -> Option<String> {
+        Some(format!("tile:{}", serde_json::to_string(self).unwrap()))
+    }
+
     fn paths(&self) -> Vec<Path> {
         self.definition.as_ref()
             .expect("You must call set_definition() before using paths()")
@@ -226,6 +327,12 @@ impl TileSpec for Tile {
             .is_lawson()
     }
 
+    fn icons(&self) -> Vec<Icon> {
+        self.definition.as_ref()
+            .expect("You must call set_definition() before using icons()")
+            .icons()
+    }
+
     fn get_text(&self, id: &str) -> &str {
         match self.text.get(id) {
             Some(s) => s,
@@ -258,6 +365,7 @@ pub struct TileDefinition {
     stops: Vec<Stop>,
     is_lawson: bool,
     text: Vec<Text>,
+    icons: Vec<Icon>,
 }
 
 impl Default for TileDefinition {
@@ -269,6 +377,7 @@ impl Default for TileDefinition {
             stops: vec![],
             is_lawson: false,
             text: vec![],
+            icons: vec![],
         }
     }
 }
@@ -278,6 +387,7 @@ impl TileSpec for TileDefinition {
     fn cities(&self) -> Vec<City> { self.cities.clone() }
     fn stops(&self) -> Vec<Stop> { self.stops.clone() }
     fn is_lawson(&self) -> bool { self.is_lawson }
+    fn icons(&self) -> Vec<Icon> { self.icons.clone() }
     fn color(&self) -> colors::Color { colors::GROUND }
     fn set_name(&mut self, name: String) { self.name = name; }
     fn name(&self) -> &str { self.name.as_str() }
@@ -322,6 +432,10 @@ pub struct Path {
     pub end_control: Option<Coordinate>,
     #[serde(default)]
     is_bridge: bool,
+    #[serde(default)]
+    track: TrackType,
+    #[serde(default)]
+    links: Option<Vec<topology::Link>>,
 }
 
 impl Path {
@@ -340,6 +454,16 @@ impl Path {
         self.is_bridge
     }
 
+    /// The kind of track that the path is
+    pub fn track(&self) -> TrackType {
+        self.track
+    }
+
+    /// The edges, cities and stops that the path is declared to go through
+    pub fn links(&self) -> Option<&Vec<topology::Link>> {
+        self.links.as_ref()
+    }
+
     /// The radius of the corner made by the path
     pub fn radius(&self) -> f64 {
         let gentle_curve = 2.0_f64.sqrt() / 2.0;
@@ -362,6 +486,28 @@ impl Path {
     }
 }
 
+/// Kinds of track that a path can be
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+         Deserialize, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum TrackType {
+    #[default]
+    Standard,
+    Narrow,
+    /// Track that both standard and narrow gauge trains can use
+    Dual,
+    Ferry,
+}
+
+impl TrackType {
+    /// Whether trains that run on a kind of track can use this track
+    pub fn carries(self, gauge: TrackType) -> bool {
+        self == gauge || (self == TrackType::Dual &&
+                          (gauge == TrackType::Standard ||
+                           gauge == TrackType::Narrow))
+    }
+}
+
 /// City on the tile
 ///
 /// A city is a collection of circles where tokens can be put down. A city
@@ -441,17 +587,49 @@ impl Text {
     }
 }
 
+/// Reference to an icon that is supplied by a game
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Icon {
+    pub name: String,
+    position: Coordinate,
+    #[serde(default="Icon::default_scale")]
+    pub scale: f64,
+}
+
+impl Icon {
+    /// The coordinate of the icon in hexagon-space.
+    pub fn position(&self) -> na::Vector3<f64> {
+        self.position.as_vector()
+    }
+
+    pub fn default_scale() -> f64 {
+        1.0
+    }
+}
+
 /// Track which shows revenue for different phases
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RevenueTrack {
     position: Coordinate,
-    pub yellow: String,
-    pub green: Option<String>,
-    pub russet: Option<String>,
-    pub grey: Option<String>,
+    pub phases: Vec<RevenuePhase>,
+}
+
+/// Single box on a revenue track
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RevenuePhase {
+    pub color: String,
+    pub value: String,
+    /// Annotation such as a bonus or the trains that the revenue applies to
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub label: Option<String>,
 }
 
 impl RevenueTrack {
+    pub fn new(position: Coordinate, phases: Vec<RevenuePhase>)
+            -> RevenueTrack {
+        RevenueTrack { position, phases }
+    }
+
     /// The coordinate of the track in hexagon-space.
     pub fn position(&self) -> na::Vector3<f64> {
         self.position.as_vector()
@@ -459,7 +637,7 @@ impl RevenueTrack {
 }
 
 /// Terrain on a tile
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Terrain {
     position: Coordinate,
     #[serde(rename="type")]
@@ -468,14 +646,29 @@ pub struct Terrain {
 }
 
 impl Terrain {
+    pub fn new(position: Coordinate, terrain_type: TerrainType,
+               cost: String) -> Terrain {
+        Terrain { position, terrain_type, cost }
+    }
+
     /// The coordinate of the terrain in hexagon-space.
     pub fn position(&self) -> na::Vector3<f64> {
         self.position.as_vector()
     }
+
+    /// The numerical value of the cost, which is the first number in it so
+    /// that currency symbols are ignored and `$40/$80` costs 40
+    pub fn cost_value(&self) -> u32 {
+        self.cost.chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse().unwrap_or(0)
+    }
 }
 
 /// Types of terrain that can be present
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(rename_all="lowercase")]
 pub enum TerrainType {
     Rough,
@@ -483,48 +676,280 @@ pub enum TerrainType {
     Mountain,
     River,
     Marsh,
+    Forest,
+    Lake,
+    Desert,
+    Swamp,
+    Tunnel,
+}
+
+/// Serialize a map with its keys in order so that files are stable
+pub fn serialize_sorted<S, V>(map: &HashMap<String, V>, serializer: S)
+        -> Result<S::Ok, S::Error>
+        where S: serde::Serializer, V: serde::Serialize {
+    use self::serde::Serialize;
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
 }
 
 /// Reads and parses all tile definitions in ./tiledefs/
 pub fn definitions(options: &super::Options)
         -> HashMap<String, TileDefinition> {
+    read_definitions(options).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+/// Reads and parses all tile definitions in ./tiledefs/
+pub fn read_definitions(options: &super::Options)
+        -> Result<HashMap<String, TileDefinition>, String> {
     println!("Reading tile definitions from file...");
-    let def_files: Vec<PathBuf> = match fs::read_dir("tiledefs") {
-        Err(err) => {
-            eprintln!("Couldn't open tile definitions directory: {:?}",
-                      err.kind());
-            process::exit(1);
-        }
-        Ok(paths) => {
-            paths.map(|path| path.unwrap().path()).collect()
-        },
-    };
-    // Read and parse each file
-    let mut definitions = HashMap::new();
+    let mut def_files: Vec<PathBuf> = fs::read_dir("tiledefs")
+        .map_err(|err| {
+            format!("Couldn't open tile definitions directory: {:?}",
+                    err.kind())
+        })?
+        .map(|path| path.unwrap().path())
+        .collect();
+    // A fixed order so the same error is reported on every run
+    def_files.sort();
+    // Read each file, templates are expanded into several definitions
+    let mut raw: HashMap<String, (Map<String, Value>, String)> =
+        HashMap::new();
     for def in &def_files {
         // Ignore non .yaml files
-        if def.extension().unwrap() != "yaml" {
-            continue;
+        match def.extension().and_then(|e| e.to_str()) {
+            Some("yaml") => {}
+            Some("toml") => {
+                eprintln!("Skipping {}, TOML tile definitions have to be \
+                          converted with `map18xx migrate`",
+                          def.to_string_lossy());
+                continue;
+            }
+            _ => continue,
         }
+        let filename = def.to_string_lossy().to_string();
         if options.verbose {
             println!("Parsing definition {}",
                      def.file_stem().unwrap().to_string_lossy());
         }
 
         // Read yaml file
-        let file = File::open(def).unwrap_or_else(|err| {
-            eprintln!("Couldn't open {}: {:?}", def.to_string_lossy(),
-                      err.kind());
-            process::exit(1);
-        });
-        let mut tile: TileDefinition = serde_yaml::from_reader(file)
-            .unwrap_or_else(|err| {
-                eprintln!("Error parsing {}: {}", def.to_string_lossy(), err);
-                process::exit(1);
-        });
-        tile.set_name(String::from(def.file_stem()
-                                   .unwrap().to_string_lossy()));
-        definitions.insert(String::from(tile.name()), tile);
-    }
-    definitions
+        let file = File::open(def).map_err(|err| {
+            format!("Couldn't open {}: {:?}", filename, err.kind())
+        })?;
+        let value: Value = serde_yaml::from_reader(file).map_err(|err| {
+            format!("Error parsing {}: {}", filename, err)
+        })?;
+        let expanded = read_definition_file(value, def).map_err(|err| {
+            format!("Error in {}: {}", filename, err)
+        })?;
+        for (name, definition) in expanded {
+            if let Some((_, other)) = raw.insert(name.clone(),
+                                                 (definition,
+                                                  filename.clone())) {
+                return Err(format!("Tile definition {} is defined in both \
+                                   {} and {}", name, other, filename));
+            }
+        }
+    }
+
+    // Resolve which definitions extend other definitions
+    let mut resolved = HashMap::new();
+    let mut definitions = HashMap::new();
+    let mut names: Vec<&String> = raw.keys().collect();
+    names.sort();
+    for name in names {
+        let value = resolve_definition(name, &raw, &mut resolved,
+                                       &mut vec![])
+            .map_err(|err| format!("Error in {}: {}", raw[name].1, err))?;
+        let mut tile: TileDefinition = serde_json::from_value(
+                Value::Object(value))
+            .map_err(|err| {
+                format!("Error parsing tile definition {} in {}: {}", name,
+                        raw[name].1, err)
+            })?;
+        tile.set_name(name.clone());
+        topology::check_links(&tile).map_err(|err| {
+            format!("Error in tile definition {} in {}: {}", name,
+                    raw[name].1, err)
+        })?;
+        definitions.insert(name.clone(), tile);
+    }
+    Ok(definitions)
+}
+
+/// Definitions together with their names, as they are written in a file
+type NamedDefinitions = Vec<(String, Map<String, Value>)>;
+
+/// The definitions in a file, named after the file or, for templates, after
+/// the `name` of each set of parameters
+fn read_definition_file(value: Value, filename: &FilePath)
+        -> Result<NamedDefinitions, String> {
+    schema::check(schema::Kind::TileDefinition, &value,
+                  &filename.to_string_lossy())?;
+    let mut object = match value {
+        Value::Object(object) => object,
+        _ => return Err(String::from("a tile definition must be an object")),
+    };
+    object.remove("version");
+    let template = match object.remove("template") {
+        None => {
+            let name = filename.file_stem().unwrap().to_string_lossy();
+            return Ok(vec![(name.to_string(), object)]);
+        }
+        Some(template) => template,
+    };
+    let parameters = match object.remove("expand") {
+        Some(Value::Array(parameters)) => parameters,
+        _ => return Err(String::from("a template needs an 'expand' array")),
+    };
+    let mut expanded = vec![];
+    for parameters in parameters {
+        let parameters = match parameters {
+            Value::Object(parameters) => parameters,
+            _ => return Err(String::from("the parameters of a template must \
+                                         be objects")),
+        };
+        let name = parameters.get("name").and_then(|n| n.as_str())
+            .ok_or("each set of parameters needs a 'name'")?
+            .to_string();
+        match substitute(&template, &parameters) {
+            Value::Object(definition) => expanded.push((name, definition)),
+            _ => return Err(String::from("a template must be an object")),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Fill in the parameters of a template
+///
+/// A string that is only a parameter, like `"{position}"`, is replaced by
+/// the value of the parameter, which may be a number or a hex-space
+/// coordinate. Parameters inside longer strings are replaced by their text.
+fn substitute(value: &Value, parameters: &Map<String, Value>) -> Value {
+    match value {
+        Value::String(text) => {
+            if text.starts_with('{') && text.ends_with('}') {
+                if let Some(value) = parameters.get(&text[1..text.len() - 1]) {
+                    return value.clone();
+                }
+            }
+            let mut text = text.clone();
+            for (key, value) in parameters.iter() {
+                let replacement = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                text = text.replace(&format!("{{{}}}", key), &replacement);
+            }
+            Value::String(text)
+        }
+        Value::Array(values) => Value::Array(
+            values.iter().map(|v| substitute(v, parameters)).collect()),
+        Value::Object(object) => Value::Object(
+            object.iter()
+                .map(|(k, v)| (k.clone(), substitute(v, parameters)))
+                .collect()),
+        other => other.clone(),
+    }
+}
+
+/// A definition combined with the definitions that it extends
+///
+/// The paths, cities, stops and icons of a definition are added to those of
+/// the definition it extends, unless they are listed in `replace`. Text
+/// replaces the text with the same ID. Other fields are overridden.
+fn resolve_definition(name: &str,
+                      raw: &HashMap<String, (Map<String, Value>, String)>,
+                      resolved: &mut HashMap<String, Map<String, Value>>,
+                      stack: &mut Vec<String>)
+        -> Result<Map<String, Value>, String> {
+    if let Some(definition) = resolved.get(name) {
+        return Ok(definition.clone());
+    }
+    if stack.iter().any(|n| n == name) {
+        return Err(format!("tile definition {} extends itself through {}",
+                           name, stack.join(", ")));
+    }
+    let mut definition = match raw.get(name) {
+        Some((definition, _)) => definition.clone(),
+        None => return Err(format!("unknown tile definition {}", name)),
+    };
+    let base = match definition.remove("extends") {
+        None => None,
+        Some(Value::String(base)) => Some(base),
+        Some(_) => return Err(format!("{}: 'extends' must be the name of a \
+                                      tile definition", name)),
+    };
+    let replace: Vec<String> = match definition.remove("replace") {
+        None => vec![],
+        Some(replace) => serde_json::from_value(replace).map_err(|_| {
+            format!("{}: 'replace' must be a list of fields", name)
+        })?,
+    };
+    let result = match base {
+        None => definition,
+        Some(base) => {
+            stack.push(name.to_string());
+            let mut result = resolve_definition(&base, raw, resolved, stack)?;
+            stack.pop();
+            let given: Vec<String> = definition.keys().cloned().collect();
+            for (key, value) in definition {
+                let extend = !replace.contains(&key);
+                match (key.as_str(), value, result.get_mut(&key)) {
+                    ("text", Value::Array(text),
+                     Some(&mut Value::Array(ref mut base_text))) if extend => {
+                        for t in text {
+                            let id = t.get("id").cloned();
+                            match base_text.iter_mut()
+                                    .find(|b| id.is_some() &&
+                                          b.get("id") == id.as_ref()) {
+                                Some(b) => *b = t,
+                                None => base_text.push(t),
+                            }
+                        }
+                    }
+                    ("paths", Value::Array(items),
+                     Some(&mut Value::Array(ref mut base_items))) |
+                    ("cities", Value::Array(items),
+                     Some(&mut Value::Array(ref mut base_items))) |
+                    ("stops", Value::Array(items),
+                     Some(&mut Value::Array(ref mut base_items))) |
+                    ("icons", Value::Array(items),
+                     Some(&mut Value::Array(ref mut base_items)))
+                            if extend => base_items.extend(items),
+                    (_, value, _) => {
+                        result.insert(key.clone(), value);
+                    }
+                }
+            }
+            // Replacing a field without giving a new value removes it
+            for key in replace.iter().filter(|k| !given.contains(k)) {
+                result.remove(key);
+            }
+            result
+        }
+    };
+    resolved.insert(name.to_string(), result.clone());
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrain_cost() {
+        let cost = |cost: &str| {
+            Terrain::new(Coordinate::Named(String::from("C")),
+                         TerrainType::River, cost.to_string()).cost_value()
+        };
+        assert_eq!(cost("40"), 40);
+        assert_eq!(cost("$120"), 120);
+        assert_eq!(cost("40/80"), 40);
+        assert_eq!(cost("$40 / $80"), 40);
+        assert_eq!(cost("free"), 0);
+        assert_eq!(cost(""), 0);
+    }
 }
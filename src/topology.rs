@@ -0,0 +1,747 @@
+//! Which parts of a tile are linked by track
+//!
+//! Paths are drawn as curves between arbitrary points in hexagon-space. The
+//! topology of a tile describes the same track in terms of the edges, cities
+//! and stops that it links, which is what matters when comparing tiles or
+//! applying the rules of a game.
+extern crate nalgebra as na;
+extern crate serde;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use self::na::{Vector2, Vector3};
+use self::serde::de::{self, Deserialize, Deserializer};
+
+use draw::consts::{C, STOP_SIZE, TOKEN_SIZE};
+use tile;
+use tile::{TileSpec, TrackType};
+
+/// Number of points at which a path is checked for cities and stops
+const SAMPLES: u32 = 32;
+/// Largest distance between a point and the middle of an edge for the point
+/// to be on the edge
+const EDGE_DISTANCE: f64 = 0.05;
+
+/// Place on a tile that track can lead to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Node {
+    /// Middle of an edge, numbered in the order of `tile::DIRECTIONS`
+    Edge(usize),
+    /// City, numbered in the order of `TileSpec::cities`
+    City(usize),
+    /// Stop, numbered in the order of `TileSpec::stops`
+    Stop(usize),
+}
+
+impl FromStr for Node {
+    type Err = String;
+
+    /// Parse a direction such as `NE`, or `city 0` or `stop 1`
+    fn from_str(s: &str) -> Result<Node, String> {
+        if let Some(i) = tile::DIRECTIONS.iter().position(|d| *d == s) {
+            return Ok(Node::Edge(i));
+        }
+        let mut words = s.split_whitespace();
+        let number = words.nth(1).and_then(|n| n.parse().ok());
+        match (s.split_whitespace().next(), number) {
+            (Some("city"), Some(n)) => Ok(Node::City(n)),
+            (Some("stop"), Some(n)) => Ok(Node::Stop(n)),
+            _ => Err(format!("'{}' is not an edge, city or stop", s)),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Edge(e) => write!(f, "{}", tile::DIRECTIONS[e]),
+            Node::City(c) => write!(f, "city {}", c),
+            Node::Stop(s) => write!(f, "stop {}", s),
+        }
+    }
+}
+
+/// Place that a path goes through, as written in the `links` of a path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Link {
+    Node(Node),
+    /// The centre of the tile where lawson track meets
+    Centre,
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Link::Node(node) => write!(f, "{}", node),
+            Link::Centre => write!(f, "C"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Link {
+    fn deserialize<D>(deserializer: D) -> Result<Link, D::Error>
+            where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        if s == "C" {
+            Ok(Link::Centre)
+        } else {
+            s.parse().map(Link::Node).map_err(de::Error::custom)
+        }
+    }
+}
+
+/// Nodes that are linked by one kind of track
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Track {
+    pub kind: TrackType,
+    pub nodes: Vec<Node>,
+}
+
+/// Track of a tile in terms of the places that it links
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topology {
+    /// Edges that track leaves the tile through
+    pub exits: Vec<usize>,
+    /// The edges, cities and stops that each path goes through, in order
+    pub paths: Vec<Track>,
+    /// Groups of nodes that are linked by track without passing through a
+    /// city or a stop. Different kinds of track are never joined.
+    pub connections: Vec<Track>,
+}
+
+/// End of a part of a path, either a node or a point where paths meet
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum End {
+    Node(Node),
+    Point(i64, i64),
+}
+
+/// Where paths meet in the centre of the tile
+const CENTRE: End = End::Point(0, 0);
+
+/// Position of a hexagon-space coordinate in the plane, with flat-topped
+/// hexes
+fn to_plane(v: Vector3<f64>) -> Vector2<f64> {
+    let h = 0.5 * 3.0_f64.sqrt();
+    Vector2::new(v.x + 0.5 * v.y - 0.5 * v.z, -h * v.y - h * v.z)
+}
+
+/// Point on a path, `t` goes from 0 at the start to 1 at the end
+fn point_on(path: &tile::Path, t: f64) -> Vector2<f64> {
+    let (p0, p3) = (path.start(), path.end());
+    let p1 = match path.start_control {
+        None => path.radius() * C * p0,
+        Some(ref point) => point.as_vector(),
+    };
+    let p2 = match path.end_control {
+        None => path.radius() * C * p3,
+        Some(ref point) => point.as_vector(),
+    };
+    let s = 1.0 - t;
+    to_plane(s * s * s * p0 + 3.0 * s * s * t * p1 + 3.0 * s * t * t * p2
+             + t * t * t * p3)
+}
+
+/// Size of the area around a node in which track reaches the node
+fn reach(cities: &[tile::City], node: Node) -> f64 {
+    match node {
+        Node::City(i) => TOKEN_SIZE * cities[i].circles.max(1) as f64,
+        Node::Stop(_) => STOP_SIZE,
+        Node::Edge(_) => EDGE_DISTANCE,
+    }
+}
+
+/// Every node of a tile together with its position
+fn nodes(cities: &[tile::City],
+         stops: &[tile::Stop]) -> Vec<(Node, Vector2<f64>)> {
+    let edges = tile::DIRECTIONS.iter().enumerate()
+        .map(|(i, d)| {
+            (Node::Edge(i),
+             to_plane(tile::Coordinate::Named(d.to_string()).as_vector()))
+        });
+    let cities = cities.iter().enumerate()
+        .map(|(i, c)| (Node::City(i), to_plane(c.position())));
+    let stops = stops.iter().enumerate()
+        .map(|(i, s)| (Node::Stop(i), to_plane(s.position())));
+    edges.chain(cities).chain(stops).collect()
+}
+
+/// What an end point of a path is on, edges come before cities and stops
+fn classify(point: Vector2<f64>, nodes: &[(Node, Vector2<f64>)],
+            cities: &[tile::City]) -> End {
+    nodes.iter()
+        .find(|&&(node, pos)| (point - pos).norm() < reach(cities, node))
+        .map(|&(node, _)| End::Node(node))
+        .unwrap_or_else(|| {
+            End::Point((point.x * 100.0).round() as i64,
+                       (point.y * 100.0).round() as i64)
+        })
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// The places that a path goes through according to its drawing
+///
+/// Cities and stops that the path passes on the way are included in the
+/// order that they are passed. Lawson track also meets other track in the
+/// centre of the tile.
+fn trace(path: &tile::Path, is_lawson: bool, nodes: &[(Node, Vector2<f64>)],
+         cities: &[tile::City]) -> Vec<End> {
+    let start = classify(point_on(path, 0.0), nodes, cities);
+    let end = classify(point_on(path, 1.0), nodes, cities);
+    let joins = is_lawson && !path.is_bridge() && start != CENTRE &&
+        end != CENTRE;
+    let mut passed: Vec<(u32, End)> = vec![];
+    for i in 1..SAMPLES {
+        let point = point_on(path, i as f64 / SAMPLES as f64);
+        if joins && point.norm() < EDGE_DISTANCE &&
+                !passed.iter().any(|&(_, e)| e == CENTRE) {
+            passed.push((i, CENTRE));
+        }
+        for &(node, pos) in nodes.iter() {
+            if let Node::Edge(_) = node {
+                continue;
+            }
+            let found = End::Node(node);
+            if found == start || found == end ||
+                    passed.iter().any(|&(_, e)| e == found) {
+                continue;
+            }
+            if (point - pos).norm() < reach(cities, node) {
+                passed.push((i, found));
+            }
+        }
+    }
+    passed.sort_by_key(|&(i, _)| i);
+    Some(start).into_iter()
+        .chain(passed.into_iter().map(|(_, e)| e))
+        .chain(Some(end))
+        .collect()
+}
+
+/// Determine the topology of a tile
+///
+/// Paths that have `links` go through the places that are listed there,
+/// other paths are traced from their drawing. A path that passes through a
+/// city or stop is split there, paths that meet at a point that isn't a city
+/// or stop are joined.
+pub fn derive<T: TileSpec + ?Sized>(tile: &T) -> Topology {
+    let cities = tile.cities();
+    let stops = tile.stops();
+    let nodes = nodes(&cities, &stops);
+
+    let mut links: Vec<(End, End, TrackType)> = vec![];
+    let mut paths: Vec<Track> = vec![];
+    for path in tile.paths() {
+        let chain: Vec<End> = match path.links() {
+            Some(declared) => declared.iter()
+                .map(|link| match *link {
+                    Link::Node(node) => End::Node(node),
+                    Link::Centre => CENTRE,
+                })
+                .collect(),
+            None => trace(&path, tile.is_lawson(), &nodes, &cities),
+        };
+        paths.push(Track {
+            kind: path.track(),
+            nodes: chain.iter()
+                .filter_map(|end| match *end {
+                    End::Node(node) => Some(node),
+                    End::Point(..) => None,
+                })
+                .collect(),
+        });
+        for pair in chain.windows(2) {
+            links.push((pair[0], pair[1], path.track()));
+        }
+    }
+
+    // Links of the same kind that meet at a point are part of the same
+    // connection
+    let mut parents: Vec<usize> = (0..links.len()).collect();
+    let mut points: HashMap<(End, TrackType), usize> = HashMap::new();
+    for (i, &(a, b, kind)) in links.iter().enumerate() {
+        for end in [a, b].iter() {
+            if let End::Point(..) = *end {
+                let other = *points.entry((*end, kind)).or_insert(i);
+                let x = find(&mut parents, i);
+                let y = find(&mut parents, other);
+                parents[x] = y;
+            }
+        }
+    }
+    let mut groups: HashMap<usize, (TrackType, BTreeSet<Node>)> =
+        HashMap::new();
+    for (i, &(a, b, kind)) in links.iter().enumerate() {
+        let root = find(&mut parents, i);
+        let &mut (_, ref mut group) = groups.entry(root)
+            .or_insert_with(|| (kind, BTreeSet::new()));
+        for end in [a, b].iter() {
+            if let End::Node(node) = *end {
+                group.insert(node);
+            }
+        }
+    }
+
+    let exits: BTreeSet<usize> = links.iter()
+        .flat_map(|&(a, b, _)| vec![a, b])
+        .filter_map(|end| match end {
+            End::Node(Node::Edge(e)) => Some(e),
+            _ => None,
+        })
+        .collect();
+    let mut connections: Vec<Track> = groups.into_iter()
+        .map(|(_, (kind, group))| Track {
+            kind,
+            nodes: group.into_iter().collect(),
+        })
+        .filter(|track| track.nodes.len() > 1)
+        .collect();
+    connections.sort();
+    connections.dedup();
+    Topology { exits: exits.into_iter().collect(), paths, connections }
+}
+
+/// Check that the cities and stops in the declared `links` of the paths of
+/// a tile exist
+pub fn check_links(tile: &dyn TileSpec) -> Result<(), String> {
+    let (cities, stops) = (tile.cities().len(), tile.stops().len());
+    for (i, path) in tile.paths().iter().enumerate() {
+        for link in path.links().into_iter().flat_map(|links| links.iter()) {
+            let exists = match *link {
+                Link::Node(Node::City(c)) => c < cities,
+                Link::Node(Node::Stop(s)) => s < stops,
+                _ => true,
+            };
+            if !exists {
+                return Err(format!("path {} links to {}, which doesn't \
+                                   exist", i, link));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Give a node a new number
+fn renumber(node: Node, edge: &dyn Fn(usize) -> usize,
+            city: &dyn Fn(usize) -> usize,
+            stop: &dyn Fn(usize) -> usize) -> Node {
+    match node {
+        Node::Edge(e) => Node::Edge(edge(e)),
+        Node::City(c) => Node::City(city(c)),
+        Node::Stop(s) => Node::Stop(stop(s)),
+    }
+}
+
+impl Topology {
+    /// The topology of the tile after mirroring it in the line through the
+    /// north and south edges and then rotating it clockwise by a number of
+    /// sixths
+    pub fn transformed(&self, rotation: usize, mirror: bool) -> Topology {
+        let edge = |e: usize| {
+            let e = if mirror { (6 - e) % 6 } else { e };
+            (e + rotation) % 6
+        };
+        let mut exits: Vec<usize> = self.exits.iter().map(|&e| edge(e))
+            .collect();
+        exits.sort();
+        let paths = self.paths.iter()
+            .map(|path| Track {
+                kind: path.kind,
+                nodes: path.nodes.iter()
+                    .map(|&node| renumber(node, &edge, &|c| c, &|s| s))
+                    .collect(),
+            })
+            .collect();
+        Topology {
+            exits,
+            paths,
+            connections: self.relabeled(&edge, &|c| c, &|s| s),
+        }
+    }
+
+    /// Whether track on the tile leaves through an edge
+    pub fn has_exit(&self, edge: usize) -> bool {
+        self.exits.contains(&edge)
+    }
+
+    /// Whether two nodes are linked without passing through a city or stop
+    pub fn connected(&self, a: Node, b: Node) -> bool {
+        self.connections.iter()
+            .any(|track| track.nodes.contains(&a) && track.nodes.contains(&b))
+    }
+
+    /// Whether two nodes are linked by track that trains of a gauge can use
+    pub fn connected_by(&self, a: Node, b: Node, gauge: TrackType) -> bool {
+        self.connections.iter()
+            .filter(|track| track.kind.carries(gauge))
+            .any(|track| track.nodes.contains(&a) && track.nodes.contains(&b))
+    }
+
+    /// Connections with their edges, cities and stops renumbered
+    fn relabeled(&self, edge: &dyn Fn(usize) -> usize,
+                 city: &dyn Fn(usize) -> usize,
+                 stop: &dyn Fn(usize) -> usize) -> Vec<Track> {
+        let mut connections: Vec<Track> = self.connections.iter()
+            .map(|track| {
+                let mut nodes: Vec<Node> = track.nodes.iter()
+                    .map(|&node| renumber(node, edge, city, stop))
+                    .collect();
+                nodes.sort();
+                Track { kind: track.kind, nodes }
+            })
+            .collect();
+        connections.sort();
+        connections
+    }
+}
+
+/// All orderings of the numbers below `n`
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for permutation in permutations(n - 1) {
+        for i in 0..n {
+            let mut p = permutation.clone();
+            p.insert(i, n - 1);
+            result.push(p);
+        }
+    }
+    result
+}
+
+/// Position of each value in the sorted list of distinct values
+fn ranks<T: Ord + Clone>(values: &[T]) -> Vec<usize> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    values.iter()
+        .map(|value| sorted.iter().position(|v| v == value).unwrap())
+        .collect()
+}
+
+/// Numberings of the cities and stops of a tile that its shape is the
+/// smallest of, as the new number of each city followed by each stop
+///
+/// Cities and stops are first told apart by their circles and by what they
+/// are connected to, which doesn't depend on how they are numbered. Only
+/// those that can't be told apart that way and are connected to other cities
+/// or stops are tried in every order, the others are interchangeable.
+fn numberings(topology: &Topology, circles: &[u32],
+              stops: usize) -> Vec<Vec<usize>> {
+    let cities = circles.len();
+    let nodes = cities + stops;
+    let index = |node: Node| match node {
+        Node::Edge(_) => None,
+        Node::City(c) => Some(c),
+        Node::Stop(s) => Some(cities + s),
+    };
+    let initial: Vec<(bool, u32)> = (0..nodes)
+        .map(|n| if n < cities { (false, circles[n]) } else { (true, 0) })
+        .collect();
+    let mut classes = ranks(&initial);
+    // Whether a city or stop shares a connection with another one
+    let linked: Vec<bool> = (0..nodes)
+        .map(|n| topology.connections.iter().any(|track| {
+            track.nodes.iter().any(|&node| index(node) == Some(n)) &&
+                track.nodes.iter().any(|&node| {
+                    index(node).is_some_and(|i| i != n)
+                })
+        }))
+        .collect();
+    loop {
+        let signatures: Vec<_> = (0..nodes)
+            .map(|n| {
+                let mut tracks: Vec<_> = topology.connections.iter()
+                    .filter(|track| {
+                        track.nodes.iter().any(|&node| index(node) == Some(n))
+                    })
+                    .map(|track| {
+                        let mut others: Vec<(bool, usize)> = track.nodes
+                            .iter()
+                            .filter(|&&node| index(node) != Some(n))
+                            .map(|&node| match (node, index(node)) {
+                                (_, Some(i)) => (true, classes[i]),
+                                (Node::Edge(e), None) => (false, e),
+                                _ => unreachable!(),
+                            })
+                            .collect();
+                        others.sort();
+                        (track.kind, others)
+                    })
+                    .collect();
+                tracks.sort();
+                (classes[n], tracks)
+            })
+            .collect();
+        let refined = ranks(&signatures);
+        let count = |classes: &[usize]| {
+            classes.iter().max().map_or(0, |&max| max + 1)
+        };
+        let done = count(&refined) == count(&classes);
+        classes = refined;
+        if done {
+            break;
+        }
+    }
+
+    // Number the classes in order, cities come first because they started
+    // out smaller than stops
+    let mut numberings = vec![vec![0; nodes]];
+    let mut next = 0;
+    for class in 0..classes.iter().max().map_or(0, |&max| max + 1) {
+        let members: Vec<usize> = (0..nodes)
+            .filter(|&n| classes[n] == class)
+            .collect();
+        let orders = if members.iter().any(|&n| linked[n]) {
+            permutations(members.len())
+        } else {
+            vec![(0..members.len()).collect()]
+        };
+        let members = &members;
+        numberings = numberings.iter()
+            .flat_map(|numbering| orders.iter().map(move |order| {
+                let mut numbering = numbering.clone();
+                for (i, &m) in order.iter().enumerate() {
+                    numbering[members[m]] = next + i;
+                }
+                numbering
+            }))
+            .collect();
+        next += members.len();
+    }
+    numberings
+}
+
+/// Description of a tile that doesn't depend on how its cities and stops
+/// are numbered
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Shape {
+    circles: Vec<u32>,
+    stops: usize,
+    exits: Vec<usize>,
+    connections: Vec<Track>,
+}
+
+impl Shape {
+    pub fn new(topology: &Topology, circles: &[u32], stops: usize) -> Shape {
+        let cities = circles.len();
+        numberings(topology, circles, stops).into_iter()
+            .map(|labels| {
+                let mut ordered = vec![0; cities];
+                for (c, &n) in circles.iter().enumerate() {
+                    ordered[labels[c]] = n;
+                }
+                Shape {
+                    circles: ordered,
+                    stops,
+                    exits: topology.exits.clone(),
+                    connections: topology.relabeled(
+                        &|e| e, &|c| labels[c],
+                        &|s| labels[cities + s] - cities),
+                }
+            })
+            .min()
+            .unwrap()
+    }
+
+    /// The shape of a tile
+    pub fn of(tile: &dyn TileSpec) -> Shape {
+        let circles: Vec<u32> = tile.cities().iter().map(|c| c.circles)
+            .collect();
+        Shape::new(&tile.topology(), &circles, tile.stops().len())
+    }
+}
+
+/// The ways a tile can be turned over and rotated, as mirror and rotation
+pub fn symmetries() -> Vec<(usize, bool)> {
+    [false, true].iter()
+        .flat_map(|&mirror| (0..6).map(move |r| (r, mirror)))
+        .collect()
+}
+
+/// A shape that is the same for all rotations and mirror images of a tile
+pub fn canonical(tile: &dyn TileSpec) -> Shape {
+    let topology = tile.topology();
+    let circles: Vec<u32> = tile.cities().iter().map(|c| c.circles).collect();
+    let stops = tile.stops().len();
+    symmetries().into_iter()
+        .map(|(r, m)| Shape::new(&topology.transformed(r, m), &circles, stops))
+        .min()
+        .unwrap()
+}
+
+/// How a tile has to be mirrored and rotated to have the same shape as
+/// another tile, if it can be
+pub fn transformation(tile: &dyn TileSpec,
+                      other: &dyn TileSpec) -> Option<(usize, bool)> {
+    let topology = tile.topology();
+    let circles: Vec<u32> = tile.cities().iter().map(|c| c.circles).collect();
+    let stops = tile.stops().len();
+    let target = Shape::of(other);
+    symmetries().into_iter().find(|&(r, m)| {
+        Shape::new(&topology.transformed(r, m), &circles, stops) == target
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use game::PlacedTile;
+    use tile::{self, TileDefinition};
+    use super::*;
+
+    fn definition(json: &str) -> TileDefinition {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Tile with a city in the centre and track to the south and south east
+    fn city_tile() -> TileDefinition {
+        definition(r#"{
+            "paths": [
+                {"start": "C", "end": "S"},
+                {"start": "C", "end": "SE"}
+            ],
+            "cities": [
+                {"circles": 1, "text_id": "1", "position": "C",
+                 "revenue_position": [0.0, 0.6, 0.0]}
+            ]
+        }"#)
+    }
+
+    #[test]
+    fn derive_traced_paths() {
+        let topology = city_tile().topology();
+        assert_eq!(topology.exits, vec![2, 3]);
+        assert!(topology.connected(Node::City(0), Node::Edge(3)));
+        assert!(!topology.connected(Node::Edge(2), Node::Edge(3)));
+    }
+
+    #[test]
+    fn placed_tile_is_rotated() {
+        let definition = city_tile();
+        let placed = PlacedTile::new_from(&definition)
+            .set_orientation(tile::direction_to_angle("NE"));
+        assert_eq!(placed.exits(), vec![3, 4]);
+        let topology = placed.topology();
+        assert!(topology.connected(Node::City(0), Node::Edge(4)));
+        assert!(!topology.connected(Node::City(0), Node::Edge(2)));
+
+        let placed = PlacedTile::new_from(&definition)
+            .set_orientation(tile::direction_to_angle("NW"));
+        assert_eq!(placed.exits(), vec![1, 2]);
+    }
+
+    #[test]
+    fn declared_links() {
+        let tile = definition(r#"{
+            "paths": [
+                {"start": "N", "end": "S", "links": ["N", "city 0", "S"]}
+            ],
+            "cities": [
+                {"circles": 2, "text_id": "1", "position": "C",
+                 "revenue_position": [0.0, 0.6, 0.0]}
+            ]
+        }"#);
+        assert!(check_links(&tile).is_ok());
+        let topology = tile.topology();
+        assert_eq!(topology.paths[0].nodes,
+                   vec![Node::Edge(0), Node::City(0), Node::Edge(3)]);
+    }
+
+    #[test]
+    fn bad_link_index() {
+        let tile = definition(r#"{
+            "paths": [
+                {"start": "N", "end": "C", "links": ["N", "city 1"]},
+                {"start": "S", "end": "C", "links": ["S", "stop 0"]}
+            ],
+            "cities": [
+                {"circles": 1, "text_id": "1", "position": "C",
+                 "revenue_position": [0.0, 0.6, 0.0]}
+            ]
+        }"#);
+        assert_eq!(check_links(&tile),
+                   Err(String::from("path 0 links to city 1, which doesn't \
+                                    exist")));
+    }
+
+    /// Topology with standard track linking groups of nodes
+    fn linked(exits: Vec<usize>, connections: Vec<Vec<Node>>) -> Topology {
+        Topology {
+            exits,
+            paths: vec![],
+            connections: connections.into_iter()
+                .map(|mut nodes| {
+                    nodes.sort();
+                    Track { kind: TrackType::Standard, nodes }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn shape_ignores_numbering() {
+        // Two cities linked to each other, one of them also to the north
+        let first = linked(vec![0, 3], vec![
+            vec![Node::Edge(0), Node::City(0)],
+            vec![Node::City(0), Node::City(1)],
+            vec![Node::City(1), Node::Edge(3)],
+        ]);
+        let second = linked(vec![0, 3], vec![
+            vec![Node::Edge(0), Node::City(1)],
+            vec![Node::City(1), Node::City(0)],
+            vec![Node::City(0), Node::Edge(3)],
+        ]);
+        assert_eq!(Shape::new(&first, &[2, 1], 0),
+                   Shape::new(&second, &[1, 2], 0));
+        assert!(Shape::new(&first, &[2, 1], 0) !=
+                Shape::new(&second, &[2, 1], 0));
+    }
+
+    #[test]
+    fn shape_of_linked_cities_that_look_alike() {
+        // A chain of three cities, the outer two can only be told apart by
+        // the stop that one of them is linked to
+        let chain = |order: [usize; 3]| linked(vec![], vec![
+            vec![Node::City(order[0]), Node::City(order[1])],
+            vec![Node::City(order[1]), Node::City(order[2])],
+            vec![Node::City(order[2]), Node::Stop(0)],
+        ]);
+        let shape = Shape::new(&chain([0, 1, 2]), &[1, 1, 1], 1);
+        assert_eq!(Shape::new(&chain([2, 0, 1]), &[1, 1, 1], 1), shape);
+        assert_eq!(Shape::new(&chain([1, 2, 0]), &[1, 1, 1], 1), shape);
+    }
+
+    #[test]
+    fn shape_of_many_cities() {
+        // Every ordering of twelve cities would be far too many to try
+        let cities = |offset: usize| linked(
+            (0..6).collect(),
+            (0..12).map(|c| vec![Node::Edge(c % 6),
+                                 Node::City((c + offset) % 12)])
+                .collect());
+        let circles = [1; 12];
+        assert_eq!(Shape::new(&cities(0), &circles, 0),
+                   Shape::new(&cities(5), &circles, 0));
+    }
+
+    #[test]
+    fn parse_links() {
+        assert_eq!("SW".parse(), Ok(Node::Edge(4)));
+        assert_eq!("stop 2".parse(), Ok(Node::Stop(2)));
+        assert!("city".parse::<Node>().is_err());
+        assert!("W".parse::<Node>().is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Redrawing files while the files that they are drawn from are edited
+//!
+//! Changes are found by comparing the modification times of every file in
+//! the watched directories every half second, which works the same on every
+//! platform and doesn't need any extra dependencies.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Time between two checks for changes
+const INTERVAL: u64 = 500;
+
+/// Modification times of all files in a list of files and directories
+fn snapshot(paths: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    let mut files = BTreeMap::new();
+    let mut todo: Vec<PathBuf> = paths.to_vec();
+    while let Some(path) = todo.pop() {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            // Files can disappear while they are being saved
+            Err(_) => continue,
+        };
+        if metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                todo.extend(entries.filter_map(|e| e.ok())
+                            .map(|e| e.path()));
+            }
+        } else if let Ok(modified) = metadata.modified() {
+            files.insert(path, modified);
+        }
+    }
+    files
+}
+
+/// Files that were added, changed or removed between two snapshots
+fn changes(old: &BTreeMap<PathBuf, SystemTime>,
+           new: &BTreeMap<PathBuf, SystemTime>) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = new.iter()
+        .filter(|&(path, modified)| old.get(path) != Some(modified))
+        .map(|(path, _)| path.clone())
+        .chain(old.keys().filter(|path| !new.contains_key(*path)).cloned())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Keeps track of the files in a list of files and directories
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Watcher {
+        let files = snapshot(&paths);
+        Watcher { paths, files }
+    }
+
+    /// Wait until files change and return the files that changed
+    ///
+    /// Editors often write a file in several steps, so the files have to
+    /// stay the same for one interval before the changes are reported.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        let interval = Duration::from_millis(INTERVAL);
+        let mut changed: Vec<PathBuf> = vec![];
+        loop {
+            thread::sleep(interval);
+            let files = snapshot(&self.paths);
+            let new = changes(&self.files, &files);
+            self.files = files;
+            if new.is_empty() && !changed.is_empty() {
+                changed.sort();
+                changed.dedup();
+                return changed;
+            }
+            changed.extend(new);
+        }
+    }
+}
+
+/// Call `render` with the files that changed every time that files in
+/// `paths` change, errors are shown and then the files are watched again
+pub fn watch<F>(paths: Vec<PathBuf>, mut render: F) -> !
+        where F: FnMut(&[PathBuf]) -> Result<(), String> {
+    let mut watcher = Watcher::new(paths);
+    loop {
+        println!("Watching for changes, press Ctrl-C to stop");
+        let changed = watcher.wait();
+        for file in changed.iter() {
+            println!("{} changed", file.to_string_lossy());
+        }
+        if let Err(err) = render(&changed) {
+            eprintln!("{}", err);
+        }
+    }
+}
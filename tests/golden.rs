@@ -0,0 +1,122 @@
+//! Compares the files that map18xx draws with the files in `tests/golden`
+//!
+//! After a change that is meant to alter the output, run the tests with
+//! `UPDATE_GOLDEN=1` to replace the golden files with the files that are
+//! drawn now and review the difference before committing it.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Copy a directory and everything in it
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            fs::copy(&path, &target).unwrap();
+        }
+    }
+}
+
+/// An empty directory with the tile definitions and games to run map18xx in
+fn workspace(name: &str) -> PathBuf {
+    let dir = env::temp_dir()
+        .join(format!("map18xx-golden-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    copy_dir(Path::new("tiledefs"), &dir.join("tiledefs"));
+    copy_dir(Path::new("games"), &dir.join("games"));
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_map18xx"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "map18xx {} failed:\n{}",
+            args.join(" "), String::from_utf8_lossy(&output.stderr));
+}
+
+/// Names of the SVG files in a directory that start with `prefix`
+fn drawn_files(dir: &Path, prefix: &str) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(prefix) && name.ends_with(".svg"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Compare the files starting with `prefix` that were drawn in `dir` with
+/// the golden files
+fn compare(dir: &Path, prefix: &str) {
+    let golden = Path::new("tests").join("golden");
+    let files = drawn_files(dir, prefix);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(&golden).unwrap();
+        // Files of sheets that are no longer drawn have to go
+        for file in drawn_files(&golden, prefix) {
+            let _ = fs::remove_file(golden.join(file));
+        }
+        for file in files.iter() {
+            fs::copy(dir.join(file), golden.join(file)).unwrap();
+        }
+        fs::remove_dir_all(dir).unwrap();
+        return;
+    }
+
+    assert_eq!(files, drawn_files(&golden, prefix));
+    // The files stay in place when they are different, to inspect them
+    for file in files.iter() {
+        let drawn = fs::read_to_string(dir.join(file)).unwrap();
+        let expected = fs::read_to_string(golden.join(file)).unwrap();
+        if drawn == expected {
+            continue;
+        }
+        let line = drawn.lines().zip(expected.lines())
+            .position(|(drawn, expected)| drawn != expected)
+            .unwrap_or_else(|| drawn.lines().count()
+                                   .min(expected.lines().count()));
+        panic!("{} differs from the golden file at line {}:\n\
+               drawn:    {}\nexpected: {}", file, line + 1,
+               drawn.lines().nth(line).unwrap_or("<end of file>"),
+               expected.lines().nth(line).unwrap_or("<end of file>"));
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn assets_1830() {
+    let dir = workspace("assets-1830");
+    run(&dir, &["asset", "1830"]);
+    compare(&dir, "1830-");
+}
+
+#[test]
+fn assets_1830_sequential() {
+    let dir = workspace("assets-1830-sequential");
+    run(&dir, &["asset", "1830", "--jobs", "1"]);
+    compare(&dir, "1830-");
+}
+
+#[test]
+fn assets_debug() {
+    let dir = workspace("assets-debug");
+    run(&dir, &["asset", "debug"]);
+    compare(&dir, "debug-");
+}
+
+/// A game of 1830 with tiles and tokens from `tests/state.yaml`
+#[test]
+fn state_1830() {
+    let dir = workspace("state-1830");
+    fs::copy(Path::new("tests").join("state.yaml"), dir.join("state.yaml"))
+        .unwrap();
+    run(&dir, &["state", "state"]);
+    compare(&dir, "state-");
+}